@@ -0,0 +1,246 @@
+use crate::prelude::*;
+use std::collections::BTreeMap;
+
+/// The pixel rectangle of a node inside the atlas, the origin is the top left corner.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct FTAtlasRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// A rendered atlas, an RGBA buffer and the location of each node.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FTAtlas {
+    pub width: usize,
+    pub height: usize,
+
+    /// Maps the variable names of the rendered nodes to their cells.
+    pub tiles: BTreeMap<String, FTAtlasRect>,
+
+    #[serde(skip)]
+    pub buffer: Vec<u8>,
+}
+
+impl FTAtlas {
+    /// Returns the JSON manifest of the atlas.
+    pub fn manifest(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+/// Renders nodes of a context into the cells of a single atlas.
+#[derive(Clone, Debug)]
+pub struct FTAtlasBuilder {
+    pub cell_width: usize,
+    pub cell_height: usize,
+    /// The amount of transparent pixels around each cell.
+    pub padding: usize,
+    /// The amount of columns, 0 for a square layout.
+    pub columns: usize,
+    /// The names of the nodes to render, all faces if empty.
+    pub nodes: Vec<String>,
+}
+
+impl Default for FTAtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FTAtlasBuilder {
+    pub fn new() -> Self {
+        Self {
+            cell_width: 128,
+            cell_height: 128,
+            padding: 0,
+            columns: 0,
+            nodes: vec![],
+        }
+    }
+
+    /// Returns the names of the nodes which will be rendered, every node is only rendered once.
+    pub fn node_names(&self, ctx: &FTContext) -> Vec<String> {
+        if !self.nodes.is_empty() {
+            let mut names: Vec<String> = vec![];
+            for name in &self.nodes {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            return names;
+        }
+
+        let mut names: Vec<String> = ctx
+            .faces
+            .iter()
+            .map(|index| ctx.nodes[*index as usize].name.clone())
+            .collect();
+
+        // Fall back to the output node for scripts without faces.
+        if names.is_empty() {
+            if let Some(output) = ctx.output_index() {
+                names.push(ctx.nodes[output].name.clone());
+            }
+        }
+
+        names
+    }
+
+    /// Render the atlas.
+    pub fn build(&self, ctx: &FTContext) -> Result<FTAtlas, FTError> {
+        if self.cell_width == 0 || self.cell_height == 0 {
+            return Err(FTError::new(
+                "The cells have to be at least 1 pixel wide and high".to_string(),
+                0,
+            ));
+        }
+
+        let names = self.node_names(ctx);
+        if names.is_empty() {
+            return Err(FTError::new("There are no nodes to render".to_string(), 0));
+        }
+
+        let columns = if self.columns > 0 {
+            self.columns
+        } else {
+            (names.len() as f32).sqrt().ceil().max(1.0) as usize
+        };
+        let rows = names.len().div_ceil(columns);
+
+        let width = columns * (self.cell_width + self.padding) + self.padding;
+        let height = rows * (self.cell_height + self.padding) + self.padding;
+
+        let mut atlas = FTAtlas {
+            width,
+            height,
+            tiles: BTreeMap::default(),
+            buffer: vec![0; width * height * 4],
+        };
+
+        let mut cell = vec![0; self.cell_width * self.cell_height * 4];
+
+        for (i, name) in names.iter().enumerate() {
+            if !ctx.render_node(name, self.cell_width, self.cell_height, &mut cell) {
+                return Err(FTError::new(format!("Unknown node `{}`", name), 0));
+            }
+
+            let x = self.padding + (i % columns) * (self.cell_width + self.padding);
+            let y = self.padding + (i / columns) * (self.cell_height + self.padding);
+
+            for line in 0..self.cell_height {
+                let src = line * self.cell_width * 4;
+                let dst = ((y + line) * width + x) * 4;
+                atlas.buffer[dst..dst + self.cell_width * 4]
+                    .copy_from_slice(&cell[src..src + self.cell_width * 4]);
+            }
+
+            atlas.tiles.insert(
+                name.clone(),
+                FTAtlasRect {
+                    x,
+                    y,
+                    width: self.cell_width,
+                    height: self.cell_height,
+                },
+            );
+        }
+
+        Ok(atlas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+
+    fn context() -> FTContext {
+        let source = "let mat = Material<BSDF> : color = #FF8040;\n\
+                      let a = Shape<Box> : material = mat, length = 0.5, height = 0.5;\n\
+                      let b = Shape<Disc> : material = mat, radius = 0.3;\n\
+                      let c = Shape<Box> : material = mat, length = 1.0, height = 0.2;";
+        Compiler::new().compile(source.to_string()).unwrap()
+    }
+
+    fn builder(nodes: &[&str]) -> FTAtlasBuilder {
+        FTAtlasBuilder {
+            cell_width: 8,
+            cell_height: 6,
+            padding: 1,
+            columns: 2,
+            nodes: nodes.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn places_cells_in_a_grid() {
+        let ctx = context();
+        let atlas = builder(&["a", "b", "c"]).build(&ctx).unwrap();
+
+        // Two columns and two rows with padding around every cell.
+        assert_eq!((atlas.width, atlas.height), (2 * 9 + 1, 2 * 7 + 1));
+        assert_eq!(atlas.buffer.len(), atlas.width * atlas.height * 4);
+
+        let rect = |x, y| FTAtlasRect {
+            x,
+            y,
+            width: 8,
+            height: 6,
+        };
+        assert_eq!(atlas.tiles["a"], rect(1, 1));
+        assert_eq!(atlas.tiles["b"], rect(10, 1));
+        assert_eq!(atlas.tiles["c"], rect(1, 8));
+
+        // Every cell contains the rendered node, the padding stays transparent.
+        for (name, tile) in &atlas.tiles {
+            let mut cell = vec![0; 8 * 6 * 4];
+            ctx.render_node(name, 8, 6, &mut cell);
+            for line in 0..tile.height {
+                let src = line * tile.width * 4;
+                let dst = ((tile.y + line) * atlas.width + tile.x) * 4;
+                assert_eq!(
+                    atlas.buffer[dst..dst + tile.width * 4],
+                    cell[src..src + tile.width * 4]
+                );
+            }
+        }
+        assert_eq!(atlas.buffer[0..4], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn writes_the_manifest() {
+        let atlas = builder(&["a", "b", "c"]).build(&context()).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&atlas.manifest()).unwrap();
+
+        assert_eq!(manifest["width"], 19);
+        assert_eq!(manifest["height"], 15);
+        assert_eq!(manifest["tiles"]["b"]["x"], 10);
+        assert_eq!(manifest["tiles"]["c"]["y"], 8);
+        assert_eq!(manifest["tiles"]["c"]["width"], 8);
+        assert!(manifest.get("buffer").is_none());
+    }
+
+    #[test]
+    fn renders_duplicates_once() {
+        let atlas = builder(&["a", "b", "a"]).build(&context()).unwrap();
+
+        assert_eq!(atlas.tiles.len(), 2);
+        assert_eq!((atlas.width, atlas.height), (2 * 9 + 1, 7 + 1));
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let ctx = context();
+
+        let mut empty_cells = builder(&["a"]);
+        empty_cells.cell_width = 0;
+        assert!(empty_cells.build(&ctx).is_err());
+
+        let empty = Compiler::new().compile(String::new()).unwrap();
+        assert!(builder(&[]).build(&empty).is_err());
+
+        assert!(builder(&["missing"]).build(&ctx).is_err());
+    }
+}
@@ -1,24 +1,16 @@
 use crate::prelude::*;
 
 use crate::scanner::TokenType;
-
-#[derive(Clone, Debug)]
-pub struct FTError {
-    pub description: String,
-    pub line: u32,
-}
-
-impl FTError {
-    pub fn new(description: String, line: u32) -> Self {
-        Self { description, line }
-    }
-}
+use exmex::Express;
+use std::path::PathBuf;
 
 struct Parser {
     current: Token,
     previous: Token,
 
-    error: Option<FTError>,
+    errors: Vec<FTError>,
+    /// Set after an error, suppresses follow-up errors until we synchronized.
+    panic_mode: bool,
 }
 
 impl Parser {
@@ -26,7 +18,8 @@ impl Parser {
         Self {
             current: Token::synthetic("".to_owned()),
             previous: Token::synthetic("".to_owned()),
-            error: None,
+            errors: vec![],
+            panic_mode: false,
         }
     }
 }
@@ -37,6 +30,19 @@ pub struct Compiler {
     parser: Parser,
 
     curr_parent: Option<usize>,
+
+    /// The script we compile, if any.
+    file: Option<PathBuf>,
+    /// The directory imports are resolved against.
+    path: PathBuf,
+    /// The prefix for declared variables, i.e. `wall.` for `import "wall.ft" as wall;`.
+    namespace: String,
+    /// The canonical paths of the scripts currently being compiled, to detect cycles.
+    import_stack: Vec<PathBuf>,
+    /// The already merged imports and their namespace.
+    imported: Vec<(PathBuf, String)>,
+    /// True while compiling an imported script, which can not set the output node.
+    importing: bool,
 }
 
 impl Default for Compiler {
@@ -52,20 +58,35 @@ impl Compiler {
             parser: Parser::new(),
 
             curr_parent: None,
+
+            file: None,
+            path: PathBuf::new(),
+            namespace: String::new(),
+            import_stack: vec![],
+            imported: vec![],
+            importing: false,
         }
     }
 
-    /// Compile the given code.
-    pub fn compile(&mut self, code: String) -> Result<FTContext, FTError> {
+    /// Set the script we compile, imports are resolved relative to it.
+    pub fn set_file(&mut self, file: PathBuf) {
+        self.path = file.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        self.import_stack = vec![file.canonicalize().unwrap_or(file.clone())];
+        self.file = Some(file);
+    }
+
+    /// Compile the given code, returns all diagnostics on failure.
+    pub fn compile(&mut self, code: String) -> Result<FTContext, Vec<FTError>> {
         let mut context = FTContext::new();
 
         self.scanner = Scanner::new(code);
+        self.parser = Parser::new();
 
         self.curr_parent = None;
         self.parse(&mut context);
 
-        if self.parser.error.is_some() {
-            Err(self.parser.error.clone().unwrap())
+        if self.has_error() {
+            Err(self.parser.errors.clone())
         } else {
             Ok(context)
         }
@@ -78,16 +99,100 @@ impl Compiler {
         while !self.matches(TokenType::Eof) {
             if self.current().kind == TokenType::Let {
                 self.declaration(ctx);
+            } else if self.current().kind == TokenType::Import {
+                self.import(ctx);
             } else {
                 self.error_at_current(&format!(
                     "Unknown instruction '{}'.",
                     self.parser.current.lexeme
                 ));
+                self.advance();
             }
 
-            if self.has_error() {
-                break;
+            if self.parser.panic_mode {
+                self.synchronize();
+            }
+        }
+    }
+
+    /// Skip tokens until the start of the next statement (after a `;` or at a `let`).
+    fn synchronize(&mut self) {
+        self.parser.panic_mode = false;
+
+        while !self.check(TokenType::Eof) {
+            if self.parser.previous.kind == TokenType::Semicolon || self.check(TokenType::Let) {
+                return;
             }
+            self.advance();
+        }
+    }
+
+    /// Import (import "file.ft" [as name];)
+    fn import(&mut self, ctx: &mut FTContext) {
+        self.advance();
+
+        let Some(file_name) =
+            self.consume(TokenType::String, "Expected a file name after 'import'.")
+        else {
+            return;
+        };
+        let file_token = self.parser.previous.clone();
+        let file_name = file_name.replace('"', "");
+
+        let mut namespace = self.namespace.clone();
+        if self.check(TokenType::Identifier) && self.current().lexeme == "as" {
+            self.advance();
+            if let Some(name) =
+                self.consume(TokenType::Identifier, "Expected a namespace after 'as'.")
+            {
+                namespace += &format!("{}.", name);
+            }
+        }
+        self.consume(TokenType::Semicolon, "Expected ';' after import.");
+
+        if self.parser.panic_mode {
+            return;
+        }
+
+        let file = self.path.join(&file_name);
+        let canonical = file.canonicalize().unwrap_or(file.clone());
+
+        if self.import_stack.contains(&canonical) {
+            self.error_at(file_token, &format!("Cyclic import of '{}'.", file_name));
+            return;
+        }
+
+        // Every file is only merged once per namespace.
+        let key = (canonical.clone(), namespace.clone());
+        if self.imported.contains(&key) {
+            return;
+        }
+
+        let Ok(code) = std::fs::read_to_string(&file) else {
+            self.error_at(file_token, &format!("Error reading file '{}'.", file_name));
+            return;
+        };
+
+        let mut compiler = Compiler::new();
+        compiler.set_file(file);
+        compiler.namespace = namespace;
+        compiler.import_stack = self.import_stack.clone();
+        compiler.import_stack.push(canonical);
+        compiler.imported = std::mem::take(&mut self.imported);
+        compiler.importing = true;
+        compiler.scanner = Scanner::new(code);
+        compiler.parse(ctx);
+
+        self.imported = compiler.imported;
+        self.imported.push(key);
+        self.parser.errors.append(&mut compiler.parser.errors);
+    }
+
+    /// An optional `*` after the node type marks the output node. It is ignored in imported
+    /// scripts, so that a library can still be rendered on its own.
+    fn output_marker(&mut self, ctx: &mut FTContext) {
+        if self.matches(TokenType::Star) && !self.importing {
+            ctx.output = Some(ctx.nodes.len());
         }
     }
 
@@ -101,6 +206,16 @@ impl Compiler {
         {
             self.consume(TokenType::Equal, "Expected '='.");
 
+            // Scalar constant (let brick_w = 0.2;)
+            if self.is_constant_start(ctx) {
+                if let Some(value) = self.constant_expression(ctx) {
+                    ctx.constants
+                        .insert(format!("{}{}", self.namespace, target), value);
+                }
+                self.consume(TokenType::Semicolon, "Expected ';' after constant.");
+                return;
+            }
+
             if let Some(node_type) =
                 self.consume(TokenType::Identifier, "Expected an identifier after 'let'.")
             {
@@ -108,10 +223,7 @@ impl Compiler {
 
                 match node_type.as_str() {
                     "Shape" => {
-                        if self.check(TokenType::Star) {
-                            ctx.output = Some(ctx.nodes.len());
-                            self.advance();
-                        }
+                        self.output_marker(ctx);
 
                         self.consume(TokenType::Less, "Expected '<'.");
                         if let Some(shape) = self.consume(
@@ -125,15 +237,36 @@ impl Compiler {
                                 "Disc" => {
                                     node = Some(Node::new(NodeRole::Shape, NodeSubRole::Disc));
                                 }
-                                _ => self.error_at_current(&format!("Unknown shape '{}'.", shape)),
+                                "Triangle" => {
+                                    node = Some(Node::new(NodeRole::Shape, NodeSubRole::Triangle));
+                                }
+                                "Polygon" => {
+                                    node = Some(Node::new(NodeRole::Shape, NodeSubRole::Polygon));
+                                }
+                                "Hexagon" => {
+                                    node = Some(Node::new(NodeRole::Shape, NodeSubRole::Hexagon));
+                                }
+                                "Star" => {
+                                    node = Some(Node::new(NodeRole::Shape, NodeSubRole::Star));
+                                }
+                                "Capsule" => {
+                                    node = Some(Node::new(NodeRole::Shape, NodeSubRole::Capsule));
+                                }
+                                "Ellipse" => {
+                                    node = Some(Node::new(NodeRole::Shape, NodeSubRole::Ellipse));
+                                }
+                                "Arc" => {
+                                    node = Some(Node::new(NodeRole::Shape, NodeSubRole::Arc));
+                                }
+                                "Ring" => {
+                                    node = Some(Node::new(NodeRole::Shape, NodeSubRole::Ring));
+                                }
+                                _ => self.error(&format!("Unknown shape '{}'.", shape)),
                             }
                         }
                     }
                     "Pattern" => {
-                        if self.check(TokenType::Star) {
-                            ctx.output = Some(ctx.nodes.len());
-                            self.advance();
-                        }
+                        self.output_marker(ctx);
                         self.consume(TokenType::Less, "Expected '<'.");
                         if let Some(shape) = self.consume(
                             TokenType::Identifier,
@@ -152,17 +285,74 @@ impl Compiler {
                                 "Group" => {
                                     node = Some(Node::new(NodeRole::Pattern, NodeSubRole::Group));
                                 }
-                                _ => {
-                                    self.error_at_current(&format!("Unknown pattern '{}'.", shape))
+                                "Grid" => {
+                                    node = Some(Node::new(NodeRole::Pattern, NodeSubRole::Grid));
+                                }
+                                "Herringbone" => {
+                                    node = Some(Node::new(
+                                        NodeRole::Pattern,
+                                        NodeSubRole::Herringbone,
+                                    ));
+                                }
+                                "HexGrid" => {
+                                    node = Some(Node::new(NodeRole::Pattern, NodeSubRole::HexGrid));
+                                }
+                                "TriGrid" => {
+                                    node = Some(Node::new(NodeRole::Pattern, NodeSubRole::TriGrid));
                                 }
+                                "Voronoi" => {
+                                    node = Some(Node::new(NodeRole::Pattern, NodeSubRole::Voronoi));
+                                }
+                                "Scatter" => {
+                                    node = Some(Node::new(NodeRole::Pattern, NodeSubRole::Scatter));
+                                }
+                                "Radial" => {
+                                    node = Some(Node::new(NodeRole::Pattern, NodeSubRole::Radial));
+                                }
+                                "Random" | "Choice" => {
+                                    node = Some(Node::new(NodeRole::Pattern, NodeSubRole::Random));
+                                }
+                                "Basketweave" => {
+                                    node = Some(Node::new(
+                                        NodeRole::Pattern,
+                                        NodeSubRole::Basketweave,
+                                    ));
+                                }
+                                "Union" => {
+                                    node = Some(Node::new(NodeRole::Pattern, NodeSubRole::Union));
+                                }
+                                "Subtract" => {
+                                    node =
+                                        Some(Node::new(NodeRole::Pattern, NodeSubRole::Subtract));
+                                }
+                                "Intersect" => {
+                                    node =
+                                        Some(Node::new(NodeRole::Pattern, NodeSubRole::Intersect));
+                                }
+                                "SmoothUnion" => {
+                                    node = Some(Node::new(
+                                        NodeRole::Pattern,
+                                        NodeSubRole::SmoothUnion,
+                                    ));
+                                }
+                                "SmoothSubtract" => {
+                                    node = Some(Node::new(
+                                        NodeRole::Pattern,
+                                        NodeSubRole::SmoothSubtract,
+                                    ));
+                                }
+                                "SmoothIntersect" => {
+                                    node = Some(Node::new(
+                                        NodeRole::Pattern,
+                                        NodeSubRole::SmoothIntersect,
+                                    ));
+                                }
+                                _ => self.error(&format!("Unknown pattern '{}'.", shape)),
                             }
                         }
                     }
                     "Face" => {
-                        if self.check(TokenType::Star) {
-                            ctx.output = Some(ctx.nodes.len());
-                            self.advance();
-                        }
+                        self.output_marker(ctx);
                         self.consume(TokenType::Less, "Expected '<'.");
                         if let Some(face) = self
                             .consume(TokenType::Identifier, "Expected a valid face after 'Face'.")
@@ -189,15 +379,12 @@ impl Compiler {
                                 "MiddleY" => {
                                     node = Some(Node::new(NodeRole::Face, NodeSubRole::MiddleY));
                                 }
-                                _ => self.error_at_current(&format!("Unknown face '{}'.", face)),
+                                _ => self.error(&format!("Unknown face '{}'.", face)),
                             }
                         }
                     }
                     "Material" => {
-                        if self.check(TokenType::Star) {
-                            ctx.output = Some(ctx.nodes.len());
-                            self.advance();
-                        }
+                        self.output_marker(ctx);
                         self.consume(TokenType::Less, "Expected '<'.");
                         if let Some(shape) = self.consume(
                             TokenType::Identifier,
@@ -207,17 +394,16 @@ impl Compiler {
                                 "BSDF" => {
                                     node = Some(Node::new(NodeRole::Material, NodeSubRole::BSDF));
                                 }
-                                _ => {
-                                    self.error_at_current(&format!("Unknown material '{}'.", shape))
-                                }
+                                _ => self.error(&format!("Unknown material '{}'.", shape)),
                             }
                         }
                     }
                     "Meta" => {
                         self.consume(TokenType::Less, "Expected '<'.");
-                        if let Some(meta) = self
-                            .consume(TokenType::Identifier, "Expected a valid face after 'Face'.")
-                        {
+                        if let Some(meta) = self.consume(
+                            TokenType::Identifier,
+                            "Expected a valid meta directive after 'Meta'.",
+                        ) {
                             match meta.as_str() {
                                 "Material" => {
                                     node =
@@ -226,22 +412,20 @@ impl Compiler {
                                 "Delete" => {
                                     node = Some(Node::new(NodeRole::Meta, NodeSubRole::MetaDelete));
                                 }
-                                _ => self.error_at_current(&format!(
-                                    "Unknown meta directive '{}'.",
-                                    meta
-                                )),
+                                _ => self.error(&format!("Unknown meta directive '{}'.", meta)),
                             }
                         }
                     }
                     _ => {
-                        self.error_at_current(&format!("Unknown type '{}'.", node_type));
+                        self.error(&format!("Unknown type '{}'.", node_type));
                     }
                 }
                 self.consume(TokenType::Greater, "Expected '>'.");
 
-                if !self.has_error() {
+                if !self.parser.panic_mode {
                     // Add the new node to the context.
                     if let Some(node) = &mut node {
+                        let target = format!("{}{}", self.namespace, target);
                         node.name = target.clone();
                         ctx.variables.insert(target, ctx.nodes.len());
 
@@ -289,37 +473,30 @@ impl Compiler {
                     self.parser.current.lexeme
                 ),
             ) {
+                let property_token = self.parser.previous.clone();
+
                 // Values
                 self.consume(TokenType::Equal, "Expected '=' after property name.");
 
                 if let Some(role) = FTExpressionRole::from_string(&property) {
-                    let mut expr_str = String::new();
-
-                    loop {
-                        if self.check(TokenType::Semicolon)
-                            || self.check(TokenType::Comma)
-                            || self.check(TokenType::Eof)
-                        {
-                            break;
-                        } else {
-                            expr_str += &self.parser.current.lexeme;
-                            self.advance();
-                        }
-                    }
+                    let start = self.parser.current.clone();
+                    let (expr_str, identifiers) = self.read_expression(ctx);
 
                     //println!("{:?} {}", role, expr_str);
                     // Add the expression
-                    node.expressions.add(role, &expr_str);
-                } else if self.check(TokenType::Number)
-                    && FTExpressionRole::from_string(&property).is_none()
-                {
-                    if let Ok(number) = self.parser.current.lexeme.parse::<f32>() {
-                        //println!("{} = {}", property, number);
-                        if !node.values.add_string_based(&property, vec![number]) {
-                            self.error_at_current(&format!("Unknown property {}", property));
-                        }
+                    if let Some(expr) =
+                        self.parse_expression(&expr_str, start.clone(), &identifiers)
+                    {
+                        self.check_shape_variables(node, role, &expr, start, &identifiers);
+                        node.expressions.add(role, expr);
                     }
-                    self.advance();
+                } else if let Some(role) = FTValueRole::from_string(&property)
+                    .filter(|_| self.is_constant_start(ctx) || self.check(TokenType::Identifier))
+                {
+                    self.value_expression(node, role, ctx);
+                } else if !self.check(TokenType::Identifier) && self.is_constant_start(ctx) {
+                    self.error_at(property_token, &format!("Unknown property '{}'.", property));
+                    break;
                 } else if self.check(TokenType::Identifier)
                     || self.check(TokenType::LeftBracket)
                     || self.check(TokenType::HexColor)
@@ -334,13 +511,15 @@ impl Compiler {
                     let map_value = self.parser.current.lexeme.clone();
                     if property == "material" {
                         self.advance();
+                        let token = self.qualified_name(self.parser.previous.clone());
 
                         if map_value.to_lowercase() == "none" {
                             continue;
-                        } else if let Some(value) = ctx.variables.get(&map_value) {
-                            node.material = Some(*value as u8);
+                        } else if let Some(value) = self.lookup_variable(&token.lexeme, ctx) {
+                            node.material = Some(value as u8);
                         } else {
-                            self.error_at_current(&format!("Unknown variable ('{}').", map_value));
+                            let message = format!("Unknown variable ('{}').", token.lexeme);
+                            self.error_at(token, &message);
                         }
                     } else if property == "texture" {
                         self.advance();
@@ -362,6 +541,16 @@ impl Compiler {
                             }
                         }
                         self.advance();
+                    } else if property == "weights" {
+                        if !has_bracket {
+                            self.error_at_current("Expected '[' at beginning of weights list.");
+                            return;
+                        }
+
+                        let weights = self.read_constant_list(ctx);
+                        if !weights.is_empty() {
+                            node.values.add(FTValueRole::Weights, weights);
+                        }
                     } else if property == "content" || property == "cutout" {
                         if !has_bracket {
                             self.error_at_current("Expected '[' at beginning of content list.");
@@ -379,15 +568,31 @@ impl Compiler {
                             }
                         } else if map_value != "]" {
                             if property == "cutout" {
-                                let cutout = self.read_string_list_as_ref_list(map_value, ctx);
+                                let first = self.parser.previous.clone();
+                                let cutout = self.read_string_list_as_ref_list(first, ctx);
                                 if !cutout.is_empty() {
                                     node.values.add(FTValueRole::Cutout, vec![cutout[0] as f32]);
                                 }
                             } else {
-                                node.links = self.read_string_list_as_ref_list(map_value, ctx);
+                                let first = self.parser.previous.clone();
+                                node.links = self.read_string_list_as_ref_list(first, ctx);
                             }
                         }
                     } else {
+                        let allowed: &[&str] = match property.as_str() {
+                            "fill" => &["clip", "stretch", "center"],
+                            "direction" if node.sub_role == NodeSubRole::Stack => &["x", "y"],
+                            "direction" => &["x", "y", "both"],
+                            _ => &[],
+                        };
+                        if !allowed.is_empty() && !allowed.contains(&map_value.as_str()) {
+                            self.error_at_current(&format!(
+                                "Unknown {} '{}', expected {}.",
+                                property,
+                                map_value,
+                                allowed.join(", ")
+                            ));
+                        }
                         node.map.insert(property, vec![map_value]);
                         self.advance();
                     }
@@ -408,6 +613,305 @@ impl Compiler {
         }
     }
 
+    /// Read the source of an expression up to the next ',' or ';', constants are inlined.
+    /// Returns the source and the identifier tokens which are not constants.
+    fn read_expression(&mut self, ctx: &FTContext) -> (String, Vec<Token>) {
+        let mut identifiers = vec![];
+        let expr_str = self.read_expression_part(ctx, &mut identifiers);
+
+        if self.check(TokenType::RightParen) {
+            self.error_at_current("Unexpected ')' in expression.");
+        }
+
+        (expr_str, identifiers)
+    }
+
+    /// Read an expression up to the next ',', ';', 'let' or unmatched ')'. Parentheses and function
+    /// calls are read as a whole, so that the commas of their arguments do not end the expression.
+    fn read_expression_part(&mut self, ctx: &FTContext, identifiers: &mut Vec<Token>) -> String {
+        let mut expr_str = String::new();
+
+        loop {
+            if self.check(TokenType::Semicolon)
+                || self.check(TokenType::Comma)
+                || self.check(TokenType::RightParen)
+                || self.check(TokenType::Let)
+                || self.check(TokenType::Eof)
+            {
+                break;
+            } else if self.matches(TokenType::LeftParen) {
+                let inner = self.read_expression_part(ctx, identifiers);
+                self.consume(TokenType::RightParen, "Expected ')' after expression.");
+                expr_str += &format!("({})", inner);
+            } else if self.check(TokenType::Identifier) {
+                self.advance();
+                let token = self.qualified_name(self.parser.previous.clone());
+                if let Some(value) = self.lookup_constant(&token.lexeme, ctx) {
+                    // Inline the value of constants
+                    expr_str += &format!("({})", value);
+                } else if self.matches(TokenType::LeftParen) {
+                    let arguments = self.read_arguments(ctx, identifiers);
+                    expr_str += &self.function_call(token, arguments);
+                } else {
+                    expr_str += &token.lexeme;
+                    identifiers.push(token);
+                }
+            } else {
+                expr_str += &self.parser.current.lexeme;
+                self.advance();
+            }
+        }
+
+        expr_str
+    }
+
+    /// Read the comma separated arguments of a call up to and including the closing ')'.
+    fn read_arguments(&mut self, ctx: &FTContext, identifiers: &mut Vec<Token>) -> Vec<String> {
+        let mut arguments = vec![];
+
+        if self.matches(TokenType::RightParen) {
+            return arguments;
+        }
+
+        loop {
+            arguments.push(self.read_expression_part(ctx, identifiers));
+
+            if self.matches(TokenType::Comma) {
+                continue;
+            } else if !self.matches(TokenType::RightParen) {
+                self.error_at_current("Expected ')' after arguments.");
+            }
+            break;
+        }
+
+        arguments
+    }
+
+    /// The source of a function call. Functions with three arguments, optional arguments, the
+    /// seeded `rand()` and the noise functions are expanded to the exmex operators. The single
+    /// argument of a function with two parameters may use `uv`, `noise(uv * 4)` is
+    /// `noise(u * 4, v * 4)`.
+    fn function_call(&mut self, token: Token, mut arguments: Vec<String>) -> String {
+        let name = token.lexeme.as_str();
+
+        let arities: &[usize] = match name {
+            "clamp" | "smoothstep" => &[1, 3],
+            "mix" | "lerp" => &[3],
+            "rand" => &[0, 1, 2],
+            "noise" | "perlin" | "simplex" | "fbm" | "voronoi" => &[1, 2],
+            _ => match FTOpsFactory::arity(name) {
+                Some(1) => &[1],
+                Some(_) => &[2],
+                None => {
+                    let message = format!("Unknown function '{}'.", name);
+                    self.error_at(token, &message);
+                    return String::new();
+                }
+            },
+        };
+
+        if arguments.len() == 1 && arities.contains(&2) && contains_word(&arguments[0], "uv") {
+            let argument = arguments.remove(0);
+            arguments.push(replace_word(&argument, "uv", "u"));
+            arguments.push(replace_word(&argument, "uv", "v"));
+        }
+
+        if !arities.contains(&arguments.len()) {
+            let expected = arities
+                .iter()
+                .map(|arity| arity.to_string())
+                .collect::<Vec<String>>()
+                .join(" or ");
+            let message = format!(
+                "'{}' expects {} argument(s), got {}.",
+                name,
+                expected,
+                arguments.len()
+            );
+            self.error_at(token, &message);
+            return String::new();
+        }
+
+        let a: Vec<String> = arguments.iter().map(|a| format!("({})", a)).collect();
+        // Binary operators are written infix, exmex does not parse `max(a, min(b, c))`.
+        let binary = |op: &str, l: &str, r: &str| format!("({} {} {})", l, op, r);
+
+        match (name, a.len()) {
+            ("clamp", 1) => binary("min", &binary("max", &a[0], "0"), "1"),
+            ("clamp", 3) => binary("min", &binary("max", &a[0], &a[1]), &a[2]),
+            ("mix" | "lerp", 3) => format!("({0} + ({1} - {0}) * {2})", a[0], a[1], a[2]),
+            ("smoothstep", 3) => {
+                format!("smoothstep(({2} - {0}) / ({1} - {0}))", a[0], a[1], a[2])
+            }
+            ("rand", 0) => binary("rand", "hash", "seed"),
+            ("rand", 1) => binary("rand", &format!("(hash + {})", a[0]), "seed"),
+            // Noise is offset by the variant so that every global seed gets its own noise, the
+            // hash and the seed of the instance already include the global seed.
+            ("noise" | "perlin" | "simplex" | "fbm" | "voronoi", 1) => {
+                binary(name, &format!("({} + variant)", a[0]), "variant")
+            }
+            ("noise" | "perlin" | "simplex" | "fbm" | "voronoi" | "rand", 2) => binary(
+                name,
+                &format!("({} + variant)", a[0]),
+                &format!("({} + variant)", a[1]),
+            ),
+            (_, 2) => binary(name, &a[0], &a[1]),
+            _ => format!("{}({})", name, a.join(", ")),
+        }
+    }
+
+    /// Read the value of a value property. Constant values are evaluated right away, values which
+    /// depend on the expression parameters (`length = 0.2 + hash * 0.05`) become an expression.
+    fn value_expression(&mut self, node: &mut Node, role: FTValueRole, ctx: &FTContext) {
+        let start = self.parser.current.clone();
+        let (expr_str, identifiers) = self.read_expression(ctx);
+
+        let Some(expr) = self.parse_expression(&expr_str, start.clone(), &identifiers) else {
+            return;
+        };
+
+        if !expr.var_names().is_empty() {
+            // Faces and tiling are evaluated once, without expression parameters
+            if node.role == NodeRole::Face || role == FTValueRole::Tileable {
+                let text = self.source_since(&start, &expr_str);
+                self.error_at(
+                    start,
+                    &format!(
+                        "Face values and 'tileable' have to be constant, got '{}'.",
+                        text
+                    ),
+                );
+            } else {
+                let role = FTExpressionRole::Value(role);
+                self.check_shape_variables(node, role, &expr, start, &identifiers);
+                node.expressions.add(role, expr);
+            }
+        } else {
+            match expr.eval(&[]) {
+                Ok(value) if value.is_finite() => node.values.add(role, vec![value]),
+                _ => self.error_at(
+                    start,
+                    &format!(
+                        "'{}' is not a finite number (division by zero ?).",
+                        expr_str
+                    ),
+                ),
+            }
+        }
+    }
+
+    /// Parse the source of an expression, errors are reported at the start of the expression or
+    /// at the unknown variable.
+    fn parse_expression(
+        &mut self,
+        expr_str: &str,
+        start: Token,
+        identifiers: &[Token],
+    ) -> Option<FTFlatEx> {
+        match FTExpressions::parse(expr_str) {
+            Ok(expr) => Some(expr),
+            Err(FTExpressionError::Syntax(_)) => {
+                let text = self.source_since(&start, expr_str);
+                self.error_at(start, &format!("Invalid expression '{}'.", text));
+                None
+            }
+            Err(FTExpressionError::UnknownVariable(name)) => {
+                let token = identifiers
+                    .iter()
+                    .find(|token| token.lexeme == name)
+                    .cloned()
+                    .unwrap_or(start);
+                if name == "uv" {
+                    self.error_at(
+                        token,
+                        "'uv' can only be the single argument of a function with two parameters, like 'noise(uv * 4)'.",
+                    );
+                } else {
+                    self.error_at(token, &format!("Unknown variable ('{}').", name));
+                }
+                None
+            }
+        }
+    }
+
+    /// Report the shape variables `u`, `v` and `edge` in expressions which are evaluated before the
+    /// shape is hit. They are only known in materials and in the extrusion.
+    fn check_shape_variables(
+        &mut self,
+        node: &Node,
+        role: FTExpressionRole,
+        expr: &FTFlatEx,
+        start: Token,
+        identifiers: &[Token],
+    ) {
+        if node.role == NodeRole::Material || role == FTExpressionRole::Extrusion {
+            return;
+        }
+
+        for name in expr.var_names() {
+            if !matches!(name.as_str(), "u" | "v" | "edge") {
+                continue;
+            }
+            // 'u' and 'v' can originate from 'uv'.
+            let token = identifiers
+                .iter()
+                .find(|token| token.lexeme == *name || (name != "edge" && token.lexeme == "uv"))
+                .cloned()
+                .unwrap_or(start);
+            let message = format!(
+                "'{}' is only available in materials and in 'extrusion'.",
+                token.lexeme
+            );
+            self.error_at(token, &message);
+            return;
+        }
+    }
+
+    /// The source code from the given token up to the last consumed token on the same line, so
+    /// that expressions are reported as written and not with inlined constants and functions.
+    fn source_since(&self, start: &Token, fallback: &str) -> String {
+        let end = self.parser.previous.offset + self.parser.previous.lexeme.len();
+        let source = self.scanner.source(start.offset, end);
+        let source = source.lines().next().unwrap_or_default().trim();
+        if source.is_empty() {
+            fallback.to_string()
+        } else {
+            source.to_string()
+        }
+    }
+
+    /// Read a comma separated list of constant expressions, up to the closing ']'.
+    pub fn read_constant_list(&mut self, ctx: &FTContext) -> Vec<f32> {
+        let mut list: Vec<f32> = vec![];
+
+        loop {
+            if self.check(TokenType::Comma) {
+                self.advance();
+            } else if self.check(TokenType::Eof) {
+                break;
+            }
+
+            if self.check(TokenType::RightBracket) {
+                self.advance();
+                break;
+            } else if self.is_constant_start(ctx) {
+                if let Some(value) = self.constant_expression(ctx) {
+                    list.push(value);
+                } else {
+                    break;
+                }
+            } else {
+                self.error_at_current(&format!(
+                    "Expected ']' at end of list, got '{}'.",
+                    self.parser.current.lexeme
+                ));
+                break;
+            }
+        }
+
+        list
+    }
+
     /// Read a comma separated list of integers and take their references as link list.
     pub fn read_number_list_as_i32_list(&mut self, first: i32) -> Vec<i32> {
         let mut list: Vec<i32> = vec![first];
@@ -440,8 +944,8 @@ impl Compiler {
     }
 
     /// Read a comma separated list of strings and take their references as link list.
-    pub fn read_string_list_as_ref_list(&mut self, first: String, ctx: &FTContext) -> Vec<i32> {
-        let mut list: Vec<String> = vec![first];
+    pub fn read_string_list_as_ref_list(&mut self, first: Token, ctx: &FTContext) -> Vec<i32> {
+        let mut list: Vec<Token> = vec![self.qualified_name(first)];
 
         loop {
             if self.check(TokenType::Comma) {
@@ -451,8 +955,9 @@ impl Compiler {
             }
 
             if self.check(TokenType::Identifier) {
-                list.push(self.current().lexeme.clone());
                 self.advance();
+                let token = self.qualified_name(self.parser.previous.clone());
+                list.push(token);
             } else if self.check(TokenType::RightBracket) {
                 self.advance();
                 break;
@@ -467,17 +972,169 @@ impl Compiler {
 
         let mut values: Vec<i32> = vec![];
 
-        for i in list {
-            if let Some(value) = ctx.variables.get(&i) {
-                values.push(*value as i32);
+        for token in list {
+            if let Some(value) = self.lookup_variable(&token.lexeme, ctx) {
+                values.push(value as i32);
             } else {
-                self.error_at_current(&format!("Unknown variable ('{}').", i));
+                let message = format!("Unknown variable ('{}').", token.lexeme);
+                self.error_at(token, &message);
             }
         }
 
         values
     }
 
+    /// Extends the given identifier token with trailing `.name` parts (namespaced variables).
+    fn qualified_name(&mut self, mut token: Token) -> Token {
+        while self.check(TokenType::Dot) {
+            self.advance();
+            if let Some(part) =
+                self.consume(TokenType::Identifier, "Expected an identifier after '.'.")
+            {
+                token.lexeme = format!("{}.{}", token.lexeme, part);
+            } else {
+                break;
+            }
+        }
+        token
+    }
+
+    /// Look up a variable, names inside an imported namespace take precedence.
+    fn lookup_variable(&self, name: &str, ctx: &FTContext) -> Option<usize> {
+        if !self.namespace.is_empty() {
+            if let Some(index) = ctx.variables.get(&format!("{}{}", self.namespace, name)) {
+                return Some(*index);
+            }
+        }
+        ctx.variables.get(name).copied()
+    }
+
+    /// Look up a scalar constant, names inside an imported namespace take precedence.
+    fn lookup_constant(&self, name: &str, ctx: &FTContext) -> Option<f32> {
+        if !self.namespace.is_empty() {
+            if let Some(value) = ctx.constants.get(&format!("{}{}", self.namespace, name)) {
+                return Some(*value);
+            }
+        }
+        ctx.constants.get(name).copied()
+    }
+
+    /// Returns true if the current token starts a constant expression: a number, a sign, a
+    /// parenthesis or the (qualified) name of a constant.
+    fn is_constant_start(&self, ctx: &FTContext) -> bool {
+        match self.parser.current.kind {
+            TokenType::Number | TokenType::Minus | TokenType::Plus | TokenType::LeftParen => true,
+            TokenType::Identifier => self
+                .lookup_constant(&self.peek_qualified_name(), ctx)
+                .is_some(),
+            _ => false,
+        }
+    }
+
+    /// The qualified name starting at the current identifier (`wall.brick_w`), without consuming
+    /// any tokens.
+    fn peek_qualified_name(&self) -> String {
+        let mut name = self.parser.current.lexeme.clone();
+        if !self.scanner.next_char_is(b'.') {
+            return name;
+        }
+        let mut scanner = self.scanner.clone();
+
+        loop {
+            if scanner.scan_token(false).kind != TokenType::Dot {
+                break;
+            }
+            let part = scanner.scan_token(false);
+            if part.kind != TokenType::Identifier {
+                break;
+            }
+            name = format!("{}.{}", name, part.lexeme);
+        }
+
+        name
+    }
+
+    /// Evaluate a constant expression (+, -).
+    fn constant_expression(&mut self, ctx: &FTContext) -> Option<f32> {
+        let mut value = self.constant_term(ctx)?;
+
+        loop {
+            if self.matches(TokenType::Plus) {
+                value += self.constant_term(ctx)?;
+            } else if self.matches(TokenType::Minus) {
+                value -= self.constant_term(ctx)?;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    /// Evaluate a constant term (*, /).
+    fn constant_term(&mut self, ctx: &FTContext) -> Option<f32> {
+        let mut value = self.constant_unary(ctx)?;
+
+        loop {
+            if self.matches(TokenType::Star) {
+                value *= self.constant_unary(ctx)?;
+            } else if self.matches(TokenType::Slash) {
+                let operator = self.parser.previous.clone();
+                let divisor = self.constant_unary(ctx)?;
+                if divisor == 0.0 {
+                    self.error_at(operator, "Division by zero.");
+                    return None;
+                }
+                value /= divisor;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    /// Evaluate a constant with an optional sign.
+    fn constant_unary(&mut self, ctx: &FTContext) -> Option<f32> {
+        if self.matches(TokenType::Minus) {
+            Some(-self.constant_unary(ctx)?)
+        } else if self.matches(TokenType::Plus) {
+            self.constant_unary(ctx)
+        } else {
+            self.constant_primary(ctx)
+        }
+    }
+
+    /// Evaluate a number, a constant or a parenthesized expression.
+    fn constant_primary(&mut self, ctx: &FTContext) -> Option<f32> {
+        if self.check(TokenType::Number) {
+            let number = self.parser.current.lexeme.parse::<f32>();
+            self.advance();
+            match number {
+                Ok(number) => Some(number),
+                Err(_) => {
+                    let message = format!("Invalid number '{}'.", self.parser.previous.lexeme);
+                    self.error(&message);
+                    None
+                }
+            }
+        } else if self.matches(TokenType::LeftParen) {
+            let value = self.constant_expression(ctx)?;
+            self.consume(TokenType::RightParen, "Expected ')' after expression.")?;
+            Some(value)
+        } else if self.matches(TokenType::Identifier) {
+            let token = self.qualified_name(self.parser.previous.clone());
+            let value = self.lookup_constant(&token.lexeme, ctx);
+            if value.is_none() {
+                let message = format!("Unknown constant ('{}').", token.lexeme);
+                self.error_at(token, &message);
+            }
+            value
+        } else {
+            self.error_at_current(&format!(
+                "Expected a number, got '{}'.",
+                self.parser.current.lexeme
+            ));
+            None
+        }
+    }
+
     /// Read a hex color.
     fn hex_to_rgb_normalized(&self, hex: &str) -> Option<Vec<f32>> {
         // Ensure the string is exactly 6 characters long
@@ -577,23 +1234,366 @@ impl Compiler {
     }
 
     /// Error at the previous token
-    fn _error(&mut self, message: &str) {
+    fn error(&mut self, message: &str) {
         self.error_at(self.parser.previous.clone(), message)
     }
 
     /// Returns true if we had an error during parsing.
     fn has_error(&self) -> bool {
-        self.parser.error.is_some()
+        self.parser.errors.iter().any(|e| e.is_error())
     }
 
     /// Error at the given token
-    fn error_at(&mut self, _token: Token, message: &str) {
-        if self.parser.error.is_some() {
+    fn error_at(&mut self, token: Token, message: &str) {
+        if self.parser.panic_mode {
             return;
         }
-        self.parser.error = Some(FTError::new(
-            message.to_string(),
-            self.parser.current.line as u32,
-        ));
+        self.parser.panic_mode = true;
+
+        let mut error = FTError::at_token(message.to_string(), &token);
+        error.file.clone_from(&self.file);
+        self.parser.errors.push(error);
+    }
+}
+
+/// Returns true if the expression source contains the given identifier.
+fn contains_word(source: &str, word: &str) -> bool {
+    replace_word(source, word, "") != source
+}
+
+/// Replace the given identifier in the expression source.
+fn replace_word(source: &str, word: &str, with: &str) -> String {
+    let mut out = String::new();
+    let mut identifier = String::new();
+
+    for c in source.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() || c == '_' || c == '.' {
+            identifier.push(c);
+        } else {
+            if identifier == word {
+                out += with;
+            } else {
+                out += &identifier;
+            }
+            identifier.clear();
+            out.push(c);
+        }
+    }
+    out.pop();
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compile the source and return the line, column and description of all errors.
+    fn errors(source: &str) -> Vec<(u32, u32, String)> {
+        match Compiler::new().compile(source.to_string()) {
+            Ok(_) => vec![],
+            Err(errors) => errors
+                .into_iter()
+                .map(|error| (error.line, error.column, error.description))
+                .collect(),
+        }
+    }
+
+    /// Write the scripts into a fresh temporary directory and return the path of the first one.
+    fn write_scripts(dir: &str, scripts: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("forgedtiles_{}", dir));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, source) in scripts {
+            std::fs::write(dir.join(name), source).unwrap();
+        }
+        dir.join(scripts[0].0)
+    }
+
+    /// Compile the given script from disk.
+    fn compile_file(file: PathBuf) -> Result<FTContext, Vec<FTError>> {
+        let code = std::fs::read_to_string(&file).unwrap();
+        let mut compiler = Compiler::new();
+        compiler.set_file(file);
+        compiler.compile(code)
+    }
+
+    #[test]
+    fn valid_source_has_no_errors() {
+        let source = "let a = Shape<Box> : length = 0.2, height = 0.1;\n\
+                      let b = Pattern<Repeat> : spacing = 0.01, content = [a];";
+        assert_eq!(errors(source), vec![]);
+    }
+
+    #[test]
+    fn reports_line_and_column() {
+        let source = "let a = Shape<Box> : length = 0.2;\nlet b = Shape<Cube> : length = 0.1;";
+        assert_eq!(
+            errors(source),
+            vec![(2, 15, "Unknown shape 'Cube'.".to_string())]
+        );
+    }
+
+    #[test]
+    fn reports_multiple_errors() {
+        let source = "let mat = Material<BSDF> : modifier = hash * weird;\n\
+                      let b = Shape<Box> : material = nope, length = 0.1;\n\
+                      let c = Shape<Box> : length = 0.1 * * 2;";
+        assert_eq!(
+            errors(source),
+            vec![
+                (1, 46, "Unknown variable ('weird').".to_string()),
+                (2, 33, "Unknown variable ('nope').".to_string()),
+                (3, 31, "Invalid expression '0.1 * * 2'.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn recovers_after_an_error() {
+        // The rest of the broken statement is skipped, the next statement is parsed again.
+        let source = "let b = Shape<Cube> : length = 0.1 * * 2;\n\
+                      foo;\n\
+                      let c = Shape<Box> : length = 0.1;\n\
+                      let d = Shape<Box> : length = ;";
+        assert_eq!(
+            errors(source),
+            vec![
+                (1, 15, "Unknown shape 'Cube'.".to_string()),
+                (2, 1, "Unknown instruction 'foo'.".to_string()),
+                (4, 31, "Unknown property value, got ';'.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn folds_constant_expressions() {
+        let source = "let w = 0.2;\n\
+                      let h = (w + 0.1) / 2 - -0.05;\n\
+                      let b = Shape<Box> : length = w * 2, height = h;";
+        let ctx = Compiler::new().compile(source.to_string()).unwrap();
+
+        assert!((ctx.constants["h"] - 0.2).abs() < 1e-6);
+
+        let node = &ctx.nodes[ctx.variables["b"]];
+        assert!((node.values.get(FTValueRole::Length, vec![])[0] - 0.4).abs() < 1e-6);
+        assert!((node.values.get(FTValueRole::Height, vec![])[0] - 0.2).abs() < 1e-6);
+        assert!(!node
+            .expressions
+            .contains(FTExpressionRole::Value(FTValueRole::Length)));
+    }
+
+    #[test]
+    fn reports_constant_errors() {
+        let source = "let z = 1 / 0;\nlet b = Shape<Box> : length = missing * 2;";
+        assert_eq!(
+            errors(source),
+            vec![
+                (1, 11, "Division by zero.".to_string()),
+                (2, 31, "Unknown variable ('missing').".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_namespaced_constants_and_nodes() {
+        let main = write_scripts(
+            "namespace",
+            &[
+                (
+                    "main.ft",
+                    "import \"lib.ft\" as wall;\n\
+                     let b = Shape<Box> : material = wall.mat, length = wall.brick_w * 2;\n\
+                     let r = Pattern<Repeat> : content = [wall.brick];",
+                ),
+                (
+                    "lib.ft",
+                    "let brick_w = 0.2;\n\
+                     let mat = Material<BSDF> : color = #A08080;\n\
+                     let brick = Shape<Box> : length = brick_w;",
+                ),
+            ],
+        );
+        let ctx = compile_file(main).unwrap();
+
+        assert_eq!(ctx.constants.get("wall.brick_w"), Some(&0.2));
+        assert!(!ctx.constants.contains_key("brick_w"));
+
+        let b = &ctx.nodes[ctx.variables["b"]];
+        assert_eq!(b.material, Some(ctx.variables["wall.mat"] as u8));
+        assert!((b.values.get(FTValueRole::Length, vec![])[0] - 0.4).abs() < 1e-6);
+
+        let r = &ctx.nodes[ctx.variables["r"]];
+        assert_eq!(r.links, vec![ctx.variables["wall.brick"] as i32]);
+    }
+
+    #[test]
+    fn reports_cyclic_imports() {
+        let main = write_scripts(
+            "cycle",
+            &[
+                (
+                    "a.ft",
+                    "import \"b.ft\";\nlet a = Shape<Box> : length = 0.1;",
+                ),
+                (
+                    "b.ft",
+                    "import \"a.ft\";\nlet b = Shape<Box> : length = 0.1;",
+                ),
+            ],
+        );
+        let errors = compile_file(main).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].description, "Cyclic import of 'a.ft'.");
+        assert_eq!((errors[0].line, errors[0].column), (1, 8));
+        assert!(errors[0].file.as_ref().unwrap().ends_with("b.ft"));
+    }
+
+    #[test]
+    fn expands_function_calls() {
+        use FTExpressionParam::*;
+
+        let eval = |expression: &str, params: Vec<(FTExpressionParam, f32)>| {
+            let source = format!("let m = Material<BSDF> : modifier = {};", expression);
+            let ctx = Compiler::new().compile(source).unwrap();
+            ctx.nodes[0]
+                .expressions
+                .eval(FTExpressionRole::Modifier, params, f32::NAN)
+        };
+
+        assert_eq!(eval("clamp(hash * 4)", vec![(Hash, 0.5)]), 1.0);
+        assert_eq!(eval("clamp(hash, 0.2, 0.4)", vec![(Hash, 0.1)]), 0.2);
+        assert_eq!(eval("mix(1, 3, hash)", vec![(Hash, 0.25)]), 1.5);
+        assert_eq!(eval("smoothstep(1, 3, hash)", vec![(Hash, 2.0)]), 0.5);
+        assert_eq!(eval("max(hash, min(row, 0.2))", vec![(Row, 1.0)]), 0.2);
+        assert_eq!(eval("(hash + 1) * 2", vec![(Hash, 0.5)]), 3.0);
+        assert_eq!(
+            eval("noise(u * 4)", vec![(U, 0.3)]),
+            crate::noise::value_noise2d(vec2f(1.2, 0.0))
+        );
+        assert_eq!(
+            eval("noise((uv + 1) * 4)", vec![(U, 0.3), (V, 0.1)]),
+            crate::noise::value_noise2d(vec2f(5.2, 4.4))
+        );
+        assert_eq!(
+            eval("rand() - rand(hash, seed)", vec![(Hash, 0.3), (Seed, 0.7)]),
+            0.0
+        );
+
+        // The variant of the global seed offsets noise and random numbers.
+        assert_eq!(
+            eval("noise(u * 4)", vec![(U, 0.3), (Variant, 2.0)]),
+            crate::noise::value_noise2d(vec2f(1.2 + 2.0, 2.0))
+        );
+        assert_eq!(
+            eval("fbm(u, v)", vec![(U, 0.3), (V, 0.1), (Variant, 2.0)]),
+            crate::noise::fbm2d(vec2f(0.3 + 2.0, 0.1 + 2.0))
+        );
+        assert_ne!(
+            eval("rand(u, v)", vec![(U, 0.3), (V, 0.1), (Variant, 2.0)]),
+            eval("rand(u, v)", vec![(U, 0.3), (V, 0.1)])
+        );
+
+        assert_eq!(
+            errors("let m = Material<BSDF> : modifier = (uv * 2);"),
+            vec![(
+                1,
+                38,
+                "'uv' can only be the single argument of a function with two parameters, like 'noise(uv * 4)'."
+                    .to_string()
+            )]
+        );
+        assert_eq!(
+            errors("let m = Material<BSDF> : modifier = mix(0, 1);"),
+            vec![(1, 37, "'mix' expects 3 argument(s), got 2.".to_string())]
+        );
+    }
+
+    #[test]
+    fn missing_semicolon_does_not_swallow_the_next_declaration() {
+        let source = "let a = Shape<Box> : length = 0.2, height = 0.1\n\
+                      let b = Shape<Box> : length = 0.2;\n\
+                      let c = Pattern<Repeat> : content = [b];";
+        assert_eq!(
+            errors(source),
+            vec![(
+                2,
+                1,
+                "Expected property identifier, got 'let'. Missing ';' after declaration ?."
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_expressions_on_a_single_line() {
+        let source = "let a = Shape<Box> : length = 0.2 *\n * 2, height = 0.1;";
+        assert_eq!(
+            errors(source),
+            vec![(1, 31, "Invalid expression '0.2 *'.".to_string())]
+        );
+    }
+
+    #[test]
+    fn reports_unknown_fill_and_direction() {
+        let source = "let b = Shape<Box> : length = 0.2;\n\
+                      let s = Pattern<Stack> : direction = both, content = [b];\n\
+                      let r = Pattern<Repeat> : direction = both, fill = strech, content = [b];";
+        assert_eq!(
+            errors(source),
+            vec![
+                (
+                    2,
+                    38,
+                    "Unknown direction 'both', expected x, y.".to_string()
+                ),
+                (
+                    3,
+                    52,
+                    "Unknown fill 'strech', expected clip, stretch, center.".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_shape_variables_outside_of_materials() {
+        let source = "let m = Material<BSDF> : roughness = edge * 0.5, metallic = noise(uv * 4);\n\
+                      let b = Shape<Box> : material = m, extrusion = 0.1 + edge;\n\
+                      let c = Shape<Box> : rounding = edge * 0.1;\n\
+                      let d = Shape<Box> : length = 0.2 + u;\n\
+                      let r = Pattern<Repeat> : jitter_x = noise(uv * 4), content = [b];";
+        let message = |name: &str| {
+            format!(
+                "'{}' is only available in materials and in 'extrusion'.",
+                name
+            )
+        };
+        assert_eq!(
+            errors(source),
+            vec![
+                (3, 33, message("edge")),
+                (4, 37, message("u")),
+                (5, 44, message("uv")),
+            ]
+        );
+    }
+
+    #[test]
+    fn smoothstep_with_equal_edges_is_a_step() {
+        let eval = |hash: f32| {
+            let source = "let m = Material<BSDF> : modifier = smoothstep(0.5, 0.5, hash);";
+            let ctx = Compiler::new().compile(source.to_string()).unwrap();
+            ctx.nodes[0].expressions.eval(
+                FTExpressionRole::Modifier,
+                vec![(FTExpressionParam::Hash, hash)],
+                f32::NAN,
+            )
+        };
+
+        assert_eq!(eval(0.4), 0.0);
+        assert_eq!(eval(0.5), 1.0);
+        assert_eq!(eval(0.6), 1.0);
     }
 }
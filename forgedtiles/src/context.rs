@@ -16,10 +16,17 @@ pub struct FTContext {
     pub materials: Vec<u8>,
 
     pub variables: FxHashMap<String, usize>,
+    /// The scalar constants (`let brick_w = 0.2;`), resolved at compile time.
+    #[serde(default)]
+    pub constants: FxHashMap<String, f32>,
 
     pub output: Option<usize>,
 
     pub meta_delete: Vec<i32>,
+
+    /// The global seed, mixed into every hash. Different seeds create variations of the script.
+    #[serde(default)]
+    pub seed: u32,
 }
 
 impl Default for FTContext {
@@ -38,11 +45,35 @@ impl FTContext {
             materials: vec![],
 
             variables: FxHashMap::default(),
+            constants: FxHashMap::default(),
 
             output: None,
 
             meta_delete: vec![],
+
+            seed: 0,
+        }
+    }
+
+    /// Set the global seed.
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+    }
+
+    /// Render `count` variants of the output node in 2D, each with its own seed starting at the current seed.
+    pub fn render_variants(&self, count: u32, width: usize, height: usize) -> Vec<Vec<u8>> {
+        let mut ctx = self.clone();
+        let mut variants = vec![];
+
+        for i in 0..count {
+            ctx.set_seed(self.seed.wrapping_add(i));
+
+            let mut buffer = vec![0; width * height * 4];
+            ctx.render(width, height, &mut buffer);
+            variants.push(buffer);
         }
+
+        variants
     }
 
     /// Get the distance to a face.
@@ -55,6 +86,7 @@ impl FTContext {
     ) -> FTHitStruct {
         let mut hit = FTHitStruct {
             tile_id,
+            variant: self.variant(),
             ..Default::default()
         };
 
@@ -67,6 +99,7 @@ impl FTContext {
 
         let face_index = self.faces[face_index];
         let indices = &self.nodes[face_index as usize].links;
+        hit.tileable = self.is_tileable(face_index as usize);
 
         let face_length = self.nodes[face_index as usize]
             .values
@@ -123,6 +156,8 @@ impl FTContext {
             _ => (Vec2f::zero(), Vec2f::zero()),
         };
 
+        hit.point = p2d;
+
         let bbox_dist = crate::sdf::sdf_box2d(
             p2d,
             vec2f(pos.x, hit.face.y / 2.0),
@@ -153,10 +188,11 @@ impl FTContext {
                 .expressions
                 .eval(
                     FTExpressionRole::Extrusion,
-                    vec![
-                        (FTExpressionParam::Hash, hit.pattern_hash),
-                        (FTExpressionParam::Thickness, face_thickness),
-                    ],
+                    [
+                        hit.params(),
+                        vec![(FTExpressionParam::Thickness, face_thickness)],
+                    ]
+                    .concat(),
                     face_thickness,
                 )
                 .clamp(0.0, 10.0);
@@ -217,7 +253,7 @@ impl FTContext {
     /// Get the face normal at the given position.
     pub fn face_normal(&self, p: Vec3f, face_index: usize, tile_id: Vec2f) -> Vec3f {
         let scale = 0.5773 * 0.0005;
-        let e = vec2f(1.0 * scale, -1.0 * scale);
+        let e = vec2f(scale, -scale);
 
         // IQs normal function
 
@@ -242,30 +278,108 @@ impl FTContext {
         normalize(n)
     }
 
-    /// Get the meta data at the given position of a face.
-    pub fn meta_data_at(&self, x: i32, y: i32, width: usize, height: usize) -> Option<FTHitStruct> {
+    /// Get the index of the output node, the `*` marked node or the last node.
+    pub fn output_index(&self) -> Option<usize> {
         if self.nodes.is_empty() {
-            return None;
+            None
+        } else {
+            Some(self.output.unwrap_or(self.nodes.len() - 1))
         }
-        let output = self.output.unwrap_or(self.nodes.len() - 1);
-        let indices = &self.nodes[output].links;
+    }
 
-        let w = width as f32;
-        let h = height as f32;
+    /// Get the index of the node with the given name.
+    pub fn node_index(&self, name: &str) -> Option<usize> {
+        self.variables.get(name).copied()
+    }
+
+    /// Get the nodes to evaluate with their positions and the face dimensions when rendering the
+    /// given node in 2D. The content of a face keeps its position, a standalone shape is centered
+    /// on the tile.
+    fn render_content(&self, output: usize) -> (Vec<(usize, Vec2f)>, Vec3f) {
+        if self.nodes[output].role == Face {
+            let face_length = self.nodes[output]
+                .values
+                .get(FTValueRole::Length, vec![1.0])[0];
+
+            let face_height = self.nodes[output]
+                .values
+                .get(FTValueRole::Height, vec![1.0])[0];
+
+            let face_thickness = self.nodes[output]
+                .values
+                .get(FTValueRole::Thickness, vec![0.2])[0];
+
+            let content = self.nodes[output]
+                .links
+                .iter()
+                .map(|index| (*index as usize, Vec2f::zero()))
+                .collect();
 
-        //let length = self.get_value_default(output, FTValueRole::Length, vec![1.0])[0];
-        let height = self.get_value_default(output, FTValueRole::Height, vec![1.0])[0];
-        let p = vec2f(x as f32 / w, height - y as f32 / h);
+            (content, vec3f(face_length, face_height, face_thickness))
+        } else {
+            let pos = if self.is_shape(output) {
+                vec2f(0.5, 0.5)
+            } else {
+                Vec2f::zero()
+            };
+
+            (vec![(output, pos)], vec3f(1.0, 1.0, 1.0))
+        }
+    }
+
+    /// The point on the face of the given pixel, rows are counted from the bottom of the image.
+    fn pixel_to_face(face: Vec3f, x: usize, row: usize, width: usize, height: usize) -> Vec2f {
+        vec2f(
+            x as f32 / width as f32 * face.x,
+            row as f32 / height as f32 * face.y,
+        )
+    }
+
+    /// Get the meta data at the given position of the output face.
+    pub fn meta_data_at(&self, x: i32, y: i32, width: usize, height: usize) -> Option<FTHitStruct> {
+        self.meta_data_at_index(self.output_index()?, x, y, width, height)
+    }
+
+    /// Get the meta data at the given position of the node with the given name.
+    pub fn meta_data_at_node(
+        &self,
+        name: &str,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+    ) -> Option<FTHitStruct> {
+        self.meta_data_at_index(self.node_index(name)?, x, y, width, height)
+    }
+
+    /// Get the meta data at the given position of the given node.
+    fn meta_data_at_index(
+        &self,
+        output: usize,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+    ) -> Option<FTHitStruct> {
+        let (content, face) = self.render_content(output);
+
+        if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+            return None;
+        }
+        let p = Self::pixel_to_face(face, x as usize, height - 1 - y as usize, width, height);
 
         let mut hit = FTHitStruct {
-            face: vec3f(100.0, 100.0, 100.0),
+            face,
+            tileable: self.is_tileable(output),
+            point: p,
+            variant: self.variant(),
             ..Default::default()
         };
 
         //let mut dist = FTHitStruct::default();
         //let mut hit_index: Option<usize> = None;
-        for index in indices {
-            self.distance(*index as usize, p, Vec2f::zero(), &mut hit);
+        for (index, pos) in content {
+            self.distance(index, p, pos, &mut hit);
             if hit.distance < 0.0 {
                 //&& hit.distance < dist.distance {
                 //dist.clone_from(&hit);
@@ -274,48 +388,33 @@ impl FTContext {
             }
         }
 
-        if hit.distance <= 0.0 {
+        if hit.distance < 0.0 {
             Some(hit)
         } else {
             None
         }
     }
 
-    /// Computes the pixel at the given position.
-    pub fn face_pixel_at(&self, mut p: Vec2f) -> Option<[u8; 4]> {
-        if self.nodes.is_empty() {
-            return None;
-        }
-
-        let output = self.output.unwrap_or(self.nodes.len() - 1);
-        let indices = if self.nodes[output].role != Face {
-            vec![output as i32]
-        } else {
-            self.nodes[output].links.clone()
-        };
-
-        let face: Vec3f = if self.nodes[output].role == NodeRole::Face {
-            let face_length = self.nodes[output]
-                .values
-                .get(FTValueRole::Length, vec![1.0])[0];
-
-            let face_height = self.nodes[output]
-                .values
-                .get(FTValueRole::Height, vec![1.0])[0];
+    /// Computes the pixel of the output node at the given position.
+    pub fn face_pixel_at(&self, p: Vec2f) -> Option<[u8; 4]> {
+        self.face_pixel_at_index(self.output_index()?, p)
+    }
 
-            let face_thickness = self.nodes[output]
-                .values
-                .get(FTValueRole::Thickness, vec![0.2])[0];
+    /// Computes the pixel of the node with the given name at the given position.
+    pub fn face_pixel_at_node(&self, name: &str, p: Vec2f) -> Option<[u8; 4]> {
+        self.face_pixel_at_index(self.node_index(name)?, p)
+    }
 
-            vec3f(face_length, face_height, face_thickness)
-        } else {
-            vec3f(1.0, 1.0, 1.0)
-        };
+    /// Computes the pixel of the given node at the given position.
+    fn face_pixel_at_index(&self, output: usize, mut p: Vec2f) -> Option<[u8; 4]> {
+        let (content, face) = self.render_content(output);
 
         let mut color = Vec3f::zero();
 
         let mut hit = FTHitStruct {
             face,
+            tileable: self.is_tileable(output),
+            variant: self.variant(),
             ..Default::default()
         };
 
@@ -324,25 +423,22 @@ impl FTContext {
 
         // Scale down to one tile unit
         p.y *= face.y;
+        hit.point = p;
 
-        for index in &indices {
-            let mut pos = vec2f(0.0, 0.0);
-            if self.nodes[*index as usize].role == NodeRole::Shape {
-                pos = vec2f(0.5, 0.5);
-            }
-            self.distance(*index as usize, p, pos, &mut hit);
+        for (index, pos) in &content {
+            self.distance(*index, p, *pos, &mut hit);
         }
 
         if hit.is_cut_out {
             return None;
         }
 
-        if hit.distance <= 0.0 {
+        if hit.distance < 0.0 {
             if let Some(node) = hit.node {
                 if let Some(material) = self.nodes[node].material {
                     let spec_trans = self.nodes[material as usize].expressions.eval(
                         FTExpressionRole::Transmission,
-                        vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                        hit.params(),
                         0.0,
                     );
 
@@ -381,52 +477,33 @@ impl FTContext {
 
     /// Render the output node into as 2D
     pub fn render(&self, width: usize, height: usize, buffer: &mut [u8]) {
-        let w = width as f32;
-        let h = height as f32;
-
-        if self.nodes.is_empty() {
-            return;
+        if let Some(output) = self.output_index() {
+            self.render_index(output, width, height, buffer);
         }
-        let output = self.output.unwrap_or(self.nodes.len() - 1);
-        let indices = if self.nodes[output].role != Face {
-            vec![output as i32]
-        } else {
-            self.nodes[output].links.clone()
-        };
-
-        let face: Vec3f = if self.nodes[output].role == NodeRole::Face {
-            let face_length = self.nodes[output]
-                .values
-                .get(FTValueRole::Length, vec![1.0])[0];
-
-            let face_height = self.nodes[output]
-                .values
-                .get(FTValueRole::Height, vec![1.0])[0];
-
-            let face_thickness = self.nodes[output]
-                .values
-                .get(FTValueRole::Thickness, vec![0.2])[0];
+    }
 
-            vec3f(face_length, face_height, face_thickness)
+    /// Render the node with the given name in 2D. Returns false if the node does not exist.
+    pub fn render_node(&self, name: &str, width: usize, height: usize, buffer: &mut [u8]) -> bool {
+        if let Some(index) = self.node_index(name) {
+            self.render_index(index, width, height, buffer);
+            true
         } else {
-            vec3f(1.0, 1.0, 1.0)
-        };
+            false
+        }
+    }
+
+    /// Render the given node in 2D.
+    fn render_index(&self, output: usize, width: usize, height: usize, buffer: &mut [u8]) {
+        let (content, face) = self.render_content(output);
+        let tileable = self.is_tileable(output);
+        let variant = self.variant();
 
         buffer
             .par_rchunks_exact_mut(width * 4)
             .enumerate()
             .for_each(|(j, line)| {
                 for (i, pixel) in line.chunks_exact_mut(4).enumerate() {
-                    let i = j * width + i;
-                    let x = (i % width) as f32;
-                    let y = (i / width) as f32;
-
-                    let xx = x / w;
-                    let yy = y / h;
-
-                    let mut p = vec2f(xx, yy);
-                    p.x *= face.x;
-                    p.y *= face.y;
+                    let p = Self::pixel_to_face(face, i, j, width, height);
 
                     let mut color = vec3f(0.0, 0.0, 0.0);
 
@@ -434,21 +511,20 @@ impl FTContext {
 
                     let mut hit = FTHitStruct {
                         face,
+                        tileable,
+                        point: p,
+                        variant,
                         ..Default::default()
                     };
 
                     //let mut hit_index: Option<usize> = None;
-                    for index in &indices {
+                    for (index, pos) in &content {
                         // let mut local_hit = FTHitStruct {
                         //     face,
                         //     ..Default::default()
                         // };
 
-                        let pos = vec2f(0.0, 0.0);
-                        // if self.nodes[*index as usize].role == NodeRole::Shape {
-                        //     pos = vec2f(0.5, 0.5);
-                        // }
-                        _ = self.distance(*index as usize, p, pos, &mut hit);
+                        _ = self.distance(*index, p, *pos, &mut hit);
                         //if distance < 0.0 {
                         //&& hit.distance < dist.distance {
                         //hit.clone_from(&local_hit);
@@ -495,13 +571,7 @@ impl FTContext {
             });
     }
 
-    pub fn render_bsdf_sample(
-        &self,
-        width: usize,
-        height: usize,
-        buffer: &mut Vec<u8>,
-        samples: i32,
-    ) {
+    pub fn render_bsdf_sample(&self, width: usize, height: usize, buffer: &mut [u8], samples: i32) {
         let w = width as f32;
         let h = height as f32;
 
@@ -744,35 +814,6 @@ impl FTContext {
     pub fn distance(&self, index: usize, p: Vec2f, mut pos: Vec2f, hit: &mut FTHitStruct) -> f32 {
         let mut distance = f32::MAX;
 
-        /// Adjust the distances for shapes
-        #[inline(always)]
-        fn adjust_distances(index: usize, distance: f32, hit: &mut FTHitStruct) {
-            if distance <= 0.0 {
-                hit.distance = distance;
-                hit.is_cut_out = false;
-            }
-            if distance < hit.min_distance {
-                hit.min_distance = distance;
-                hit.node = Some(index);
-
-                hit.seed = hit.working_seed;
-                hit.seed_id = hit.working_seed_id;
-
-                hit.pattern_hash = hit.working_pattern_hash;
-                hit.pattern_id = hit.working_pattern_id;
-            }
-
-            if hit.node.is_none() {
-                hit.node = Some(index);
-
-                hit.seed = hit.working_seed;
-                hit.seed_id = hit.working_seed_id;
-
-                hit.pattern_hash = hit.working_pattern_hash;
-                hit.pattern_id = hit.working_pattern_id;
-            }
-        }
-
         // #[allow(clippy::single_match)]
         match &self.nodes[index].role {
             Shape => match &self.nodes[index].sub_role {
@@ -780,10 +821,29 @@ impl FTContext {
                     if self.meta_delete.contains(&hit.working_seed_id)
                         || self.meta_delete.contains(&hit.working_pattern_id)
                     {
+                        hit.is_cut_out = true;
                         return hit.distance;
                     }
-                    let radius = self.get_value_default(index, FTValueRole::Radius, vec![0.5])[0];
-                    distance = length(p - pos) - radius;
+                    let mut radius =
+                        self.get_value_default(index, FTValueRole::Radius, vec![0.5], hit)[0];
+
+                    radius += hit.shape_adder / 2.0;
+                    radius += self.nodes[index].expressions.eval(
+                        FTExpressionRole::RadiusModifier,
+                        hit.working_params(),
+                        0.0,
+                    );
+
+                    // Rotation and rounding have no effect on a disc.
+                    let (_, hole, _) = self.shape_modifiers(index, hit);
+                    distance = length(p - pos) - (radius - hole).max(0.0);
+
+                    if hole > 0.0 {
+                        distance = distance.abs() - hole;
+                    }
+
+                    hit.working_uv = (p - pos) / (2.0 * radius.max(0.0001)) + 0.5;
+                    hit.last_size = self.get_dim_default(index);
                     adjust_distances(index, distance, hit);
                 }
                 Box => {
@@ -794,31 +854,19 @@ impl FTContext {
                         return hit.distance;
                     }
                     let mut length =
-                        self.get_value_default(index, FTValueRole::Length, vec![1.0])[0];
+                        self.get_value_default(index, FTValueRole::Length, vec![1.0], hit)[0];
                     let mut height =
-                        self.get_value_default(index, FTValueRole::Height, vec![1.0])[0];
+                        self.get_value_default(index, FTValueRole::Height, vec![1.0], hit)[0];
 
                     length += hit.shape_adder;
                     height += hit.shape_adder;
                     // let rounding =
                     //     self.get_value_default(index, FTValueRole::Rounding, vec![0.0])[0];
-                    let rounding = self.nodes[index].expressions.eval(
-                        FTExpressionRole::Rounding,
-                        vec![(FTExpressionParam::Hash, hit.working_pattern_hash)],
-                        0.0,
-                    );
-                    let hole = self.nodes[index].expressions.eval(
-                        FTExpressionRole::Annular,
-                        vec![(FTExpressionParam::Hash, hit.working_pattern_hash)],
-                        0.0,
-                    );
-                    let rotation = self.nodes[index].expressions.eval(
-                        FTExpressionRole::Rotation,
-                        vec![(FTExpressionParam::Hash, hit.working_pattern_hash)],
-                        0.0,
-                    );
+                    let (rounding, hole, rotation) = self.shape_modifiers(index, hit);
+                    // Rotate around the center of the box.
+                    let local = crate::sdf::rot(-rotation.to_radians()) * (p - pos);
                     distance = crate::sdf::sdf_box2d(
-                        crate::sdf::rot((hit.working_pattern_hash - 0.5) * rotation) * p,
+                        local + pos,
                         pos,
                         length / 2.0 - hole,
                         height / 2.0 - hole,
@@ -829,95 +877,333 @@ impl FTContext {
                         distance = distance.abs() - hole;
                     }
 
+                    hit.working_uv =
+                        local / max(vec2f(length, height), Vec2f::one() * 0.0001) + 0.5;
+                    hit.last_size = self.get_dim_default(index);
+                    adjust_distances(index, distance, hit);
+                }
+                Triangle | Polygon | Hexagon | Star | Capsule | Ellipse | Arc | Ring => {
+                    if self.meta_delete.contains(&hit.working_seed_id)
+                        || self.meta_delete.contains(&hit.working_pattern_id)
+                    {
+                        hit.is_cut_out = true;
+                        return hit.distance;
+                    }
+
+                    let (rounding, hole, rotation) = self.shape_modifiers(index, hit);
+
+                    // Shrink the shape so that rounding and the annular hole stay inside its bounds.
+                    let inset = rounding + hole;
+                    let dim = self.nodes[index].get_shape_dim_with(&hit.working_params())
+                        + hit.shape_adder;
+                    let radius = dim.x / 2.0 - inset;
+                    let p = crate::sdf::rot(-rotation.to_radians()) * (p - pos);
+
+                    distance = match &self.nodes[index].sub_role {
+                        Triangle => crate::sdf::sdf_triangle2d(
+                            p,
+                            Vec2f::zero(),
+                            dim.x - 2.0 * inset,
+                            dim.y - 2.0 * inset,
+                        ),
+                        Polygon => {
+                            let sides =
+                                self.get_value_default(index, FTValueRole::Sides, vec![5.0], hit)
+                                    [0];
+                            crate::sdf::sdf_polygon2d(p, Vec2f::zero(), radius, sides.max(3.0))
+                        }
+                        Hexagon => crate::sdf::sdf_polygon2d(p, Vec2f::zero(), radius, 6.0),
+                        Star => {
+                            let sides =
+                                self.get_value_default(index, FTValueRole::Sides, vec![5.0], hit)
+                                    [0]
+                                .max(3.0);
+                            let ratio =
+                                self.get_value_default(index, FTValueRole::Ratio, vec![0.5], hit)
+                                    [0]
+                                .clamp(0.0, 1.0);
+                            crate::sdf::sdf_star2d(
+                                p,
+                                Vec2f::zero(),
+                                radius,
+                                sides,
+                                2.0 + ratio * (sides - 2.0),
+                            )
+                        }
+                        Capsule => crate::sdf::sdf_capsule2d(
+                            p,
+                            Vec2f::zero(),
+                            dim.x - 2.0 * inset,
+                            dim.y - 2.0 * inset,
+                        ),
+                        Ellipse => crate::sdf::sdf_ellipse2d(
+                            p,
+                            Vec2f::zero(),
+                            dim.x - 2.0 * inset,
+                            dim.y - 2.0 * inset,
+                        ),
+                        Arc => {
+                            let width =
+                                self.get_value_default(index, FTValueRole::Width, vec![0.1], hit)
+                                    [0];
+                            let angle =
+                                self.get_value_default(index, FTValueRole::Angle, vec![180.0], hit)
+                                    [0];
+                            crate::sdf::sdf_arc2d(
+                                p,
+                                Vec2f::zero(),
+                                angle.to_radians(),
+                                dim.x / 2.0 - width / 2.0,
+                                width / 2.0 - inset,
+                            )
+                        }
+                        _ => {
+                            let width =
+                                self.get_value_default(index, FTValueRole::Width, vec![0.1], hit)
+                                    [0];
+                            crate::sdf::sdf_ring2d(
+                                p,
+                                Vec2f::zero(),
+                                dim.x / 2.0 - width / 2.0,
+                                width / 2.0 - inset,
+                            )
+                        }
+                    } - rounding;
+
+                    if hole > 0.0 {
+                        distance = distance.abs() - hole;
+                    }
+
+                    hit.working_uv = p / max(dim, Vec2f::one() * 0.0001) + 0.5;
                     hit.last_size = self.get_dim_default(index);
                     adjust_distances(index, distance, hit);
                 }
                 _ => {}
             },
             Pattern => match &self.nodes[index].sub_role {
-                Repeat => {
-                    if !self.nodes[index].links.is_empty() {
-                        fn op_rep(p: Vec2f, s: f32) -> Vec2f {
-                            vec2f(p.x - s * round(p.x / s), p.y)
+                Repeat if !self.nodes[index].links.is_empty() => {
+                    let content = self.nodes[index].links[0] as usize;
+                    let dim = self.get_dim_default(content);
+                    let spacing =
+                        self.get_value_default(index, FTValueRole::Spacing, vec![0.0], hit)[0];
+                    let offset =
+                        self.get_value_default(index, FTValueRole::Offset, vec![0.0], hit)[0];
+                    let count =
+                        self.get_value_default(index, FTValueRole::Count, vec![0.0], hit)[0];
+                    let fill = self.get_string(index, "fill", "clip");
+                    let face = vec2f(hit.face.x, hit.face.y);
+
+                    let direction = self.get_string(index, "direction", "x");
+                    let repeated = [direction != "y", direction == "y" || direction == "both"];
+
+                    // The offset shifts along the main direction.
+                    let mut shift = Vec2f::zero();
+                    shift[if repeated[0] { 0 } else { 1 }] = offset;
+
+                    // The amount of repeats (0 for unlimited), the spacing and the margin per
+                    // direction. Tileable and stretched repeats fit a whole number of repeats on
+                    // the face by adjusting the spacing, centered ones add margins.
+                    let tileable = hit.tileable || self.is_tileable(index);
+                    let mut counts = Vec2f::zero();
+                    let mut spacings = vec2f(spacing, spacing);
+                    let mut margins = Vec2f::zero();
+                    for axis in 0..2 {
+                        if !repeated[axis] {
+                            continue;
                         }
-                        let content = self.nodes[index].links[0] as usize;
-                        let dim = self.get_dim_default(content);
-                        let spacing =
-                            self.get_value_default(index, FTValueRole::Spacing, vec![0.0])[0];
-                        let offset =
-                            self.get_value_default(index, FTValueRole::Offset, vec![0.0])[0];
-
-                        hit.last_size = dim;
-                        pos += vec2f(0.0, dim.y / 2.0);
-
-                        let r = op_rep(
-                            p - vec2f(dim.x / 2.0 - offset * dim.x, 0.0),
-                            dim.x + spacing,
-                        );
+                        let cell = dim[axis] + spacing;
+                        if tileable || fill == "stretch" {
+                            let mut n = count.round();
+                            if n < 1.0 {
+                                n = fitting_count(face[axis], cell, 1) as f32;
+                            }
+                            counts[axis] = n;
+                            spacings[axis] = face[axis] / n - dim[axis];
+                        } else if fill == "center" {
+                            let mut n = count.round();
+                            if n < 1.0 {
+                                n = ((face[axis] + spacing) / cell).floor().max(1.0);
+                            }
+                            counts[axis] = n;
+                            margins[axis] = (face[axis] - n * cell + spacing) / 2.0;
+                        } else {
+                            counts[axis] = count.round().max(0.0);
+                        }
+                    }
 
-                        let u = (p
-                            - vec2f(offset * dim.x, pos.y - dim.y / 2.0)
-                            - vec2f(hit.tile_id.x, 0.0))
-                            / vec2f(dim.x + spacing, dim.y + pos.y);
-                        hit.working_pattern_hash = crate::sdf::hash21(floor(u) + hit.working_seed);
-                        hit.working_pattern_id =
-                            ((hit.working_pattern_hash * 10000.0).floor() as i32) % 10000;
+                    // Find the nearest repeat, along directions which do not repeat the content
+                    // starts at pos.
+                    let cell = dim + spacings;
+                    let mut anchor = pos;
+                    let mut first = Vec2f::zero();
+                    let mut k = Vec2f::zero();
+                    for axis in 0..2 {
+                        if repeated[axis] {
+                            first[axis] = margins[axis] + dim[axis] / 2.0 - shift[axis] * dim[axis];
+                            k[axis] = ((p[axis] - anchor[axis] - first[axis]) / cell[axis]).round();
+                            if counts[axis] > 0.0 && !tileable {
+                                k[axis] = k[axis].clamp(0.0, counts[axis] - 1.0);
+                            }
+                        } else {
+                            anchor[axis] += dim[axis] / 2.0;
+                        }
+                    }
+                    let r = p - first - k * cell;
+
+                    let hash = if repeated[0] && !repeated[1] {
+                        let u = (p - vec2f(offset * dim.x, pos.y) - vec2f(hit.tile_id.x, 0.0))
+                            / vec2f(cell.x, dim.y + anchor.y);
+                        if tileable {
+                            // The cell index, wrapped so that the cells at both face borders match.
+                            let cell = ((p.x - hit.tile_id.x - dim.x / 2.0 + offset * dim.x)
+                                / cell.x)
+                                .round() as i32;
+                            let cell = vec2f(cell.rem_euclid(counts.x as i32) as f32, u.y.floor());
+                            self.hash(cell + hit.working_seed)
+                        } else {
+                            self.hash(floor(u) + hit.working_seed)
+                        }
+                    } else {
+                        let mut cell = k;
+                        if tileable {
+                            cell = vec2f(
+                                cell.x.rem_euclid(counts.x.max(1.0)),
+                                cell.y.rem_euclid(counts.y.max(1.0)),
+                            );
+                        }
+                        self.hash(cell + hit.working_seed)
+                    };
+                    hit.set_working_pattern_hash(hash);
+                    hit.working_col = k.x as i32;
+                    hit.working_row = k.y as i32;
+
+                    distance = self.distance_jittered(
+                        index,
+                        content,
+                        r,
+                        anchor,
+                        hit.working_pattern_hash,
+                        hit,
+                    );
 
-                        distance = self.distance(content, r, pos, hit);
+                    // Limited repeats report their extent.
+                    hit.last_size = dim;
+                    for axis in 0..2 {
+                        if counts[axis] > 0.0 {
+                            hit.last_size[axis] = counts[axis] * cell[axis] - spacings[axis];
+                        }
                     }
                 }
-                Stack => {
-                    if !self.nodes[index].links.is_empty() {
-                        let spacing =
-                            self.get_value_default(index, FTValueRole::Spacing, vec![0.0])[0];
-
-                        pos = hit.origin;
-                        let mut counter = 0;
-                        // let mut rng = rand::thread_rng();
-                        // hit.seed = rng.gen();
-
-                        let content = self.nodes[index].links[0] as usize;
-
-                        let mut top_end = hit.face.y;
-                        if self.nodes[content].role == Shape {
-                            let dim = self.get_dim_default(content);
-                            pos += vec2f(dim.x / 2.0, dim.y / 2.0);
-                            top_end += dim.y / 2.0;
-                        }
+                Stack if !self.nodes[index].links.is_empty() => {
+                    let mut spacing =
+                        self.get_value_default(index, FTValueRole::Spacing, vec![0.0], hit)[0];
+                    let count =
+                        self.get_value_default(index, FTValueRole::Count, vec![0.0], hit)[0];
+                    let fill = self.get_string(index, "fill", "clip");
+                    let face = vec2f(hit.face.x, hit.face.y);
+
+                    // Stack upwards or to the right.
+                    let axis = if self.get_string(index, "direction", "y") == "x" {
+                        0
+                    } else {
+                        1
+                    };
 
-                        loop {
-                            hit.working_seed = crate::sdf::hash21(pos);
-                            hit.working_seed_id =
-                                ((hit.working_seed * 10000.0).floor() as i32) % 10000;
-                            let content = self.nodes[index].links
-                                [counter % self.nodes[index].links.len()]
-                                as usize;
+                    pos = hit.origin;
+                    let mut counter = 0;
+                    // let mut rng = rand::thread_rng();
+                    // hit.seed = rng.gen();
 
-                            distance = self.distance(content, p, pos, hit);
-                            pos.y += hit.last_size.y + spacing;
+                    let content = self.nodes[index].links[0] as usize;
 
-                            //println!("{} {}", pos.y + hit.last_size.y, top_end);
-                            if pos.y + hit.last_size.y > top_end {
-                                break;
-                            }
+                    let mut top_end = face[axis];
+                    if self.is_shape(content) {
+                        let dim = self.get_dim_default(content);
+                        pos += vec2f(dim.x / 2.0, dim.y / 2.0);
+                        top_end += dim[axis] / 2.0;
+                    }
+
+                    // Fit a whole number of rows on the face by adjusting the spacing. The row
+                    // count is a multiple of the content count so that alternating rows wrap.
+                    let old_tileable = hit.tileable;
+                    hit.tileable = hit.tileable || self.is_tileable(index);
+                    let mut rows = count.round().max(0.0) as usize;
+                    let height = self.get_content_dim(content)[axis];
+                    // Rows which do not advance can only be placed a fixed number of times.
+                    if rows == 0 && height + spacing <= 0.0 {
+                        rows = self.nodes[index].links.len();
+                    }
+                    if hit.tileable {
+                        let links = self.nodes[index].links.len();
+
+                        if rows == 0 {
+                            rows = fitting_count(face[axis], height + spacing, links);
+                        }
+                        spacing = face[axis] / rows as f32 - height;
+                    } else if fill == "stretch" {
+                        if rows == 0 {
+                            rows = fitting_count(face[axis], height + spacing, 1);
+                        }
+                        spacing = face[axis] / rows as f32 - height;
+                    } else if fill == "center" {
+                        if rows == 0 {
+                            rows = ((face[axis] + spacing) / (height + spacing))
+                                .floor()
+                                .max(1.0) as usize;
+                        }
+                        pos[axis] +=
+                            (face[axis] - rows as f32 * (height + spacing) + spacing) / 2.0;
+                    }
 
-                            counter += 1;
+                    // Each row is a new instance, hashes of the previous row do not apply.
+                    let old_has_pattern_hash = hit.has_working_pattern_hash;
+                    loop {
+                        hit.working_seed = self.hash(pos);
+                        hit.working_seed_id = ((hit.working_seed * 10000.0).floor() as i32) % 10000;
+                        hit.has_working_pattern_hash = false;
+                        let content = self.nodes[index].links
+                            [counter % self.nodes[index].links.len()]
+                            as usize;
+
+                        distance =
+                            self.distance_jittered(index, content, p, pos, hit.working_seed, hit);
+                        let step = hit.last_size[axis] + spacing;
+                        pos[axis] += step;
+
+                        //println!("{} {}", pos.y + hit.last_size.y, top_end);
+                        let done = if hit.tileable || fill == "stretch" || fill == "center" {
+                            counter + 1 >= rows
+                        } else {
+                            (rows > 0 && counter + 1 >= rows)
+                                || step <= 0.0
+                                || pos[axis] + hit.last_size[axis] > top_end
+                        };
+                        if done {
+                            break;
                         }
+
+                        counter += 1;
                     }
+
+                    hit.tileable = old_tileable;
+                    hit.has_working_pattern_hash = old_has_pattern_hash;
                 }
                 Group => {
                     let mut group_pos = Vec2f::zero();
-                    group_pos.x =
-                        hit.tile_id.x + self.get_value_default(index, FTValueRole::X, vec![0.0])[0];
-                    group_pos.y = self.get_value_default(index, FTValueRole::Y, vec![0.0])[0];
+                    group_pos.x = hit.tile_id.x
+                        + self.get_value_default(index, FTValueRole::X, vec![0.0], hit)[0];
+                    group_pos.y = self.get_value_default(index, FTValueRole::Y, vec![0.0], hit)[0];
                     let old_origin = hit.origin;
                     hit.origin = group_pos;
 
-                    if let Some(cut_out_indices) = self.get_value(index, FTValueRole::Cutout) {
+                    if let Some(cut_out_indices) = self.get_value(index, FTValueRole::Cutout, hit) {
                         let cut_out_index = cut_out_indices[0] as usize;
 
                         let cut_out_dim = self.get_dim_default(cut_out_index);
-                        let mut cut_out_hit = FTHitStruct::default();
+                        let mut cut_out_hit = FTHitStruct {
+                            variant: hit.variant,
+                            ..Default::default()
+                        };
                         let cut_out_distance = self.distance(
                             cut_out_index,
                             p,
@@ -948,7 +1234,7 @@ impl FTContext {
                     for content in &self.nodes[index].links {
                         let index = *content as usize;
                         let old_group_pos = group_pos;
-                        if self.nodes[index].role == Shape {
+                        if self.is_shape(index) {
                             let dim = self.get_dim_default(index);
                             group_pos.x += dim.x / 2.0;
                             group_pos.y += dim.y / 2.0;
@@ -959,45 +1245,1652 @@ impl FTContext {
 
                     hit.origin = old_origin;
                 }
-                _ => {}
-            },
-            _ => {}
-        }
+                Grid if !self.nodes[index].links.is_empty() => {
+                    let content = self.nodes[index].links[0] as usize;
+                    let dim = self.get_content_dim(content);
+
+                    let spacing =
+                        self.get_value_default(index, FTValueRole::Spacing, vec![0.0], hit)[0];
+                    let mut spacing = vec2f(
+                        self.get_value_default(index, FTValueRole::SpacingX, vec![spacing], hit)[0],
+                        self.get_value_default(index, FTValueRole::SpacingY, vec![spacing], hit)[0],
+                    );
 
-        distance
-    }
+                    // A count of 0 fills the face.
+                    let mut columns =
+                        self.get_value_default(index, FTValueRole::Columns, vec![0.0], hit)[0]
+                            as i32;
+                    let mut rows =
+                        self.get_value_default(index, FTValueRole::Rows, vec![0.0], hit)[0] as i32;
+                    let row_offset =
+                        self.get_value_default(index, FTValueRole::RowOffset, vec![0.0], hit)[0];
+                    let column_offset =
+                        self.get_value_default(index, FTValueRole::ColumnOffset, vec![0.0], hit)[0];
+
+                    // Fit a whole number of cells on the face by adjusting the spacing.
+                    let tileable = hit.tileable || self.is_tileable(index);
+                    if tileable {
+                        columns = fitting_count(hit.face.x, dim.x + spacing.x, 1) as i32;
+                        rows = fitting_count(hit.face.y, dim.y + spacing.y, 1) as i32;
+                        spacing = vec2f(
+                            hit.face.x / columns as f32 - dim.x,
+                            hit.face.y / rows as f32 - dim.y,
+                        );
+                    }
+                    let cell_size = dim + spacing;
+
+                    // Find the cell, rows are shifted by the row offset and columns by the
+                    // column offset.
+                    let local = p - hit.origin;
+                    let col = (local.x / cell_size.x).floor();
+                    let shift_y = (col * column_offset).fract() * cell_size.y;
+                    let row = ((local.y - shift_y) / cell_size.y).floor();
+                    let shift_x = (row * row_offset).fract() * cell_size.x;
+                    let col = ((local.x - shift_x) / cell_size.x).floor();
+
+                    let outside = |cell: f32, count: i32| {
+                        count > 0 && !tileable && (cell < 0.0 || cell >= count as f32)
+                    };
+                    if outside(col, columns) || outside(row, rows) {
+                        return distance;
+                    }
 
-    /// Get a value from a node.
-    fn get_value(&self, index: usize, role: FTValueRole) -> Option<Vec<f32>> {
-        self.nodes[index].values.get_option(role)
-    }
+                    let mut cell = vec2f(col, row);
+                    if tileable {
+                        cell = vec2f(
+                            (col as i32).rem_euclid(columns) as f32,
+                            (row as i32).rem_euclid(rows) as f32,
+                        );
+                    }
 
-    /// Get a value from a node.
-    fn get_value_default(&self, index: usize, role: FTValueRole, default: Vec<f32>) -> Vec<f32> {
-        self.nodes[index].values.get(role, default)
-    }
+                    hit.working_row = cell.y as i32;
+                    hit.working_col = cell.x as i32;
+                    hit.set_working_pattern_hash(self.hash(cell + hit.working_seed));
 
-    /// Get the dimension of a node.
-    fn get_dim_default(&self, index: usize) -> Vec2f {
-        self.nodes[index].get_shape_dim()
-    }
-}
+                    let mut cell_pos = hit.origin
+                        + vec2f(col, row) * cell_size
+                        + vec2f(shift_x, shift_y)
+                        + spacing / 2.0;
+                    if self.is_shape(content) {
+                        cell_pos += dim / 2.0;
+                    }
+                    distance = self.distance_jittered(
+                        index,
+                        content,
+                        p,
+                        cell_pos,
+                        hit.working_pattern_hash,
+                        hit,
+                    );
 
-/*
-float fillMask(float dist)
-{
-    return clamp(-dist, 0.0, 1.0);
-}
+                    hit.last_size = if columns > 0 && rows > 0 {
+                        vec2f(columns as f32, rows as f32) * cell_size
+                    } else {
+                        dim
+                    };
+                }
+                Herringbone if !self.nodes[index].links.is_empty() => {
+                    let content = self.nodes[index].links[0] as usize;
+                    let dim = self.get_content_dim(content);
+                    let spacing =
+                        self.get_value_default(index, FTValueRole::Spacing, vec![0.0], hit)[0];
+
+                    // Each unit is a horizontal plank in [0, l] x [0, w] and a vertical plank in
+                    // [0, w] x [w, w + l], units repeat along (w, w) and (l, -l).
+                    let l = dim.x + spacing;
+                    let w = dim.y + spacing;
+                    let local = p - hit.origin;
+                    let b0 = ((local.x - local.y) / (2.0 * l)).floor();
+
+                    for b in [b0 - 1.0, b0, b0 + 1.0] {
+                        for vertical in [false, true] {
+                            let (a, center) = if vertical {
+                                let a = ((local.x - b * l) / w).floor();
+                                (a, vec2f(w / 2.0, w + l / 2.0))
+                            } else {
+                                let a = ((local.y + b * l) / w).floor();
+                                (a, vec2f(l / 2.0, w / 2.0))
+                            };
+                            let center = hit.origin + center + vec2f(a * w + b * l, a * w - b * l);
+
+                            hit.set_working_pattern_hash(self.hash(
+                                vec2f(2.0 * a + if vertical { 1.0 } else { 0.0 }, b)
+                                    + hit.working_seed,
+                            ));
+
+                            // Vertical planks evaluate the plank rotated by 90 degrees.
+                            let d = p - center;
+                            let q = if vertical {
+                                center + vec2f(d.y, -d.x)
+                            } else {
+                                p
+                            };
+                            distance = self.distance_jittered(
+                                index,
+                                content,
+                                q,
+                                center,
+                                hit.working_pattern_hash,
+                                hit,
+                            );
+                        }
+                    }
+                    hit.last_size = dim;
+                }
+                Basketweave if !self.nodes[index].links.is_empty() => {
+                    let content = self.nodes[index].links[0] as usize;
+                    let dim = self.get_content_dim(content);
+                    let spacing =
+                        self.get_value_default(index, FTValueRole::Spacing, vec![0.0], hit)[0];
+
+                    // Square blocks of planks, alternating between horizontal and vertical. The
+                    // planks fit best if the length is a multiple of the height plus spacing.
+                    let count = ((dim.x + spacing) / (dim.y + spacing)).round().max(1.0);
+                    let block = count * (dim.y + spacing);
+                    let local = p - hit.origin;
+                    let cell = floor(local / block);
+                    let vertical = (cell.x + cell.y).rem_euclid(2.0) == 1.0;
+
+                    // The plank inside the block.
+                    let block_pos = hit.origin + cell * block;
+                    let (k, center) = if vertical {
+                        let k = ((local.x - cell.x * block) / (block / count)).floor();
+                        (k, vec2f((k + 0.5) * block / count, block / 2.0))
+                    } else {
+                        let k = ((local.y - cell.y * block) / (block / count)).floor();
+                        (k, vec2f(block / 2.0, (k + 0.5) * block / count))
+                    };
+                    let center = block_pos + center;
 
-float innerBorderMask(float dist, float width)
-{
-    //dist += 1.0;
-    return clamp(dist + width, 0.0, 1.0) - clamp(dist, 0.0, 1.0);
-}
+                    let key = if vertical {
+                        vec2f(cell.x * count + k, cell.y + 0.5)
+                    } else {
+                        vec2f(cell.x, cell.y * count + k)
+                    };
+                    hit.set_working_pattern_hash(self.hash(key + hit.working_seed));
 
-float outerBorderMask(float dist, float width)
-{
-    //dist += 1.0;
-    return clamp(dist, 0.0, 1.0) - clamp(dist - width, 0.0, 1.0);
-}
-*/
+                    let d = p - center;
+                    let q = if vertical {
+                        center + vec2f(d.y, -d.x)
+                    } else {
+                        p
+                    };
+                    distance = self.distance_jittered(
+                        index,
+                        content,
+                        q,
+                        center,
+                        hit.working_pattern_hash,
+                        hit,
+                    );
+                    hit.last_size = dim;
+                }
+                HexGrid if !self.nodes[index].links.is_empty() => {
+                    let content = self.nodes[index].links[0] as usize;
+                    let dim = self.get_content_dim(content);
+                    let spacing =
+                        self.get_value_default(index, FTValueRole::Spacing, vec![0.0], hit)[0];
+
+                    // Pointy top hexagons, the circumradius of a cell includes the spacing.
+                    let r = dim.x / 2.0 + spacing / 3.0_f32.sqrt();
+                    let size = vec2f(3.0_f32.sqrt() * r, 3.0 * r);
+
+                    // The cells are the union of two rectangular grids, take the closer center.
+                    let local = p - hit.origin;
+                    let a = floor(local / size + 0.5);
+                    let b = floor((local - size / 2.0) / size + 0.5);
+                    let (cell, center) =
+                        if length(local - a * size) <= length(local - (b * size + size / 2.0)) {
+                            (vec2f(a.x, 2.0 * a.y), a * size)
+                        } else {
+                            (vec2f(b.x, 2.0 * b.y + 1.0), b * size + size / 2.0)
+                        };
+
+                    hit.working_row = cell.y as i32;
+                    hit.working_col = cell.x as i32;
+                    hit.set_working_pattern_hash(self.hash(cell + hit.working_seed));
+
+                    distance = self.distance_jittered(
+                        index,
+                        content,
+                        p,
+                        hit.origin + center,
+                        hit.working_pattern_hash,
+                        hit,
+                    );
+                    hit.last_size = dim;
+                }
+                TriGrid if !self.nodes[index].links.is_empty() => {
+                    let content = self.nodes[index].links[0] as usize;
+                    let dim = self.get_content_dim(content);
+                    let spacing =
+                        self.get_value_default(index, FTValueRole::Spacing, vec![0.0], hit)[0];
+
+                    // Equilateral triangles, the side of a cell includes the spacing.
+                    let side = dim.x + spacing * 3.0_f32.sqrt();
+                    let height = side * 3.0_f32.sqrt() / 2.0;
+
+                    // Every row is shifted by half a side so that the triangles line up.
+                    let local = p - hit.origin;
+                    let row = (local.y / height).floor();
+                    let v = local.y / height - row;
+                    let u = (local.x - row * side / 2.0) / side;
+                    let i = u.floor();
+                    let fu = u - i;
+
+                    // Up triangles have their base at the bottom of the row, down triangles
+                    // lie between them.
+                    let (col, down) = if fu < v / 2.0 {
+                        (i - 1.0, true)
+                    } else if fu > 1.0 - v / 2.0 {
+                        (i, true)
+                    } else {
+                        (i, false)
+                    };
+
+                    let centroid = if down {
+                        vec2f((col + 1.0) * side, 2.0 * height / 3.0)
+                    } else {
+                        vec2f((col + 0.5) * side, height / 3.0)
+                    };
+                    let centroid = hit.origin + centroid + vec2f(row * side / 2.0, row * height);
+
+                    let cell = vec2f(2.0 * col + if down { 1.0 } else { 0.0 }, row);
+                    hit.working_row = cell.y as i32;
+                    hit.working_col = cell.x as i32;
+                    hit.set_working_pattern_hash(self.hash(cell + hit.working_seed));
+
+                    // Content is centered on its bounding box, down triangles are rotated by 180
+                    // degrees around the centroid.
+                    let pos = centroid + vec2f(0.0, dim.y / 6.0);
+                    let q = if down { centroid * 2.0 - p } else { p };
+                    distance = self.distance_jittered(
+                        index,
+                        content,
+                        q,
+                        pos,
+                        hit.working_pattern_hash,
+                        hit,
+                    );
+                    hit.last_size = dim;
+                }
+                Voronoi => {
+                    let density =
+                        self.get_value_default(index, FTValueRole::Density, vec![5.0], hit)[0];
+                    let jitter =
+                        self.get_value_default(index, FTValueRole::Jitter, vec![1.0], hit)[0];
+                    let spacing =
+                        self.get_value_default(index, FTValueRole::Spacing, vec![0.0], hit)[0];
+
+                    // A whole number of cells per face wraps the cells at the face borders.
+                    let mut density = vec2f(density.max(0.001), density.max(0.001));
+                    let mut period = Vec2f::zero();
+                    if hit.tileable || self.is_tileable(index) {
+                        period = max(
+                            floor(vec2f(hit.face.x, hit.face.y) * density + 0.5),
+                            Vec2f::one(),
+                        );
+                        density = period / vec2f(hit.face.x, hit.face.y);
+                    }
+                    let cell_hash = |cell: Vec2f| {
+                        let cell = if period.x > 0.0 {
+                            vec2f(cell.x.rem_euclid(period.x), cell.y.rem_euclid(period.y))
+                        } else {
+                            cell
+                        };
+                        cell + hit.working_seed
+                    };
+                    let point = |cell: Vec2f| {
+                        let h = cell_hash(cell);
+                        vec2f(self.hash(h), self.hash(h + vec2f(37.0, 17.0))) * jitter
+                            + (1.0 - jitter) * 0.5
+                    };
+
+                    // Distance to the cell borders, https://iquilezles.org/articles/voronoilines/
+                    let x = (p - hit.origin) * density;
+                    let n = floor(x);
+                    let f = x - n;
+
+                    let mut closest = Vec2f::zero();
+                    let mut closest_cell = Vec2f::zero();
+                    let mut closest_distance = f32::MAX;
+                    for j in -1..=1 {
+                        for i in -1..=1 {
+                            let g = vec2f(i as f32, j as f32);
+                            let r = g + point(n + g) - f;
+                            let d = dot(r, r);
+                            if d < closest_distance {
+                                closest_distance = d;
+                                closest = r;
+                                closest_cell = n + g;
+                            }
+                        }
+                    }
+
+                    // The two closest borders of the cell, in face units.
+                    let mut borders = vec2f(f32::MAX, f32::MAX);
+                    for j in -2..=2 {
+                        for i in -2..=2 {
+                            let g = closest_cell - n + vec2f(i as f32, j as f32);
+                            let r = g + point(n + g) - f;
+                            if dot(closest - r, closest - r) > 0.00001 {
+                                let d = dot((closest + r) * 0.5, normalize(r - closest))
+                                    / density.x.min(density.y);
+                                if d < borders.x {
+                                    borders = vec2f(d, borders.x);
+                                } else if d < borders.y {
+                                    borders.y = d;
+                                }
+                            }
+                        }
+                    }
+
+                    hit.set_working_pattern_hash(self.hash(cell_hash(closest_cell)));
+
+                    if self.meta_delete.contains(&hit.working_seed_id)
+                        || self.meta_delete.contains(&hit.working_pattern_id)
+                    {
+                        hit.is_cut_out = true;
+                        return hit.distance;
+                    }
+
+                    // Shrink the cells by half the spacing and round the two closest borders
+                    // like the corner of a box.
+                    let (rounding, _, _) = self.shape_modifiers(index, hit);
+                    let d = Vec2f::one() * (spacing / 2.0 + rounding) - borders;
+                    distance = length(max(d, Vec2f::zero())) + min(max(d.x, d.y), 0.0) - rounding;
+
+                    hit.working_uv = Vec2f::one() * 0.5 - closest;
+                    hit.last_size = Vec2f::one() / density;
+                    adjust_distances(index, distance, hit);
+                }
+                Scatter if !self.nodes[index].links.is_empty() => {
+                    let content = self.nodes[index].links[0] as usize;
+                    let dim = self.get_content_dim(content);
+                    let face = vec2f(hit.face.x, hit.face.y);
+
+                    let min_distance = self.get_value_default(
+                        index,
+                        FTValueRole::MinDistance,
+                        vec![dim.x.max(dim.y)],
+                        hit,
+                    )[0]
+                    .max(0.001);
+                    let rotation = vec2f(
+                        self.get_value_default(index, FTValueRole::MinRotation, vec![0.0], hit)[0],
+                        self.get_value_default(index, FTValueRole::MaxRotation, vec![0.0], hit)[0],
+                    );
+                    let scale = vec2f(
+                        self.get_value_default(index, FTValueRole::MinScale, vec![1.0], hit)[0],
+                        self.get_value_default(index, FTValueRole::MaxScale, vec![1.0], hit)[0],
+                    );
+
+                    // Each grid cell holds one candidate, the cells are small enough that a
+                    // candidate has no other candidate within the minimum distance in its cell.
+                    let mut cell_size = vec2f(min_distance, min_distance) / 2.0_f32.sqrt();
+                    let mut period = Vec2f::zero();
+                    if hit.tileable || self.is_tileable(index) {
+                        period = max(floor(face / cell_size), Vec2f::one());
+                        cell_size = face / period;
+                    }
+
+                    // Thin out the candidates to the requested count or density (per unit area), the
+                    // minimum distance rejects some more so both are upper bounds.
+                    let density =
+                        if let Some(count) = self.get_value(index, FTValueRole::Count, hit) {
+                            count[0] / (face.x * face.y)
+                        } else {
+                            self.get_value_default(index, FTValueRole::Density, vec![0.0], hit)[0]
+                        };
+                    let keep = if density > 0.0 {
+                        density * cell_size.x * cell_size.y
+                    } else {
+                        1.0
+                    };
+
+                    let seed = hit.working_seed;
+                    let cell_key = |cell: Vec2f| {
+                        let cell = if period.x > 0.0 {
+                            vec2f(cell.x.rem_euclid(period.x), cell.y.rem_euclid(period.y))
+                        } else {
+                            cell
+                        };
+                        cell + seed
+                    };
+
+                    // The position and the priority of the candidate of a cell.
+                    let candidate = |cell: Vec2f| {
+                        let key = cell_key(cell);
+                        if self.hash(key + vec2f(71.3, 11.9)) >= keep {
+                            return None;
+                        }
+                        let offset = vec2f(self.hash(key), self.hash(key + vec2f(37.0, 17.0)));
+                        Some((
+                            (cell + offset) * cell_size,
+                            self.hash(key + vec2f(13.1, 53.7)),
+                        ))
+                    };
+
+                    // A candidate is accepted if no candidate with a higher priority is closer
+                    // than the minimum distance, so accepted points never violate it.
+                    let accepted = |cell: Vec2f| {
+                        let (point, priority) = candidate(cell)?;
+                        for j in -2..=2 {
+                            for i in -2..=2 {
+                                if i == 0 && j == 0 {
+                                    continue;
+                                }
+                                let other = cell + vec2f(i as f32, j as f32);
+                                if let Some((q, q_priority)) = candidate(other) {
+                                    if length(q - point) < min_distance
+                                        && (q_priority, other.x, other.y)
+                                            > (priority, cell.x, cell.y)
+                                    {
+                                        return None;
+                                    }
+                                }
+                            }
+                        }
+                        Some(point)
+                    };
+
+                    // Visit all cells whose instances can reach p.
+                    let local = p - hit.origin;
+                    let n = floor(local / cell_size);
+                    let extent = dim.x.max(dim.y) * scale.x.max(scale.y) / 2.0;
+                    let reach = (extent / cell_size.x.min(cell_size.y))
+                        .ceil()
+                        .clamp(1.0, 3.0) as i32;
+
+                    for j in -reach..=reach {
+                        for i in -reach..=reach {
+                            let cell = n + vec2f(i as f32, j as f32);
+                            let Some(point) = accepted(cell) else {
+                                continue;
+                            };
+
+                            let key = cell_key(cell);
+                            hit.set_working_pattern_hash(self.hash(key + vec2f(5.3, 91.1)));
+
+                            let angle = (rotation.x
+                                + (rotation.y - rotation.x) * self.hash(key + vec2f(23.0, 7.0)))
+                            .to_radians();
+                            let s = (scale.x
+                                + (scale.y - scale.x) * self.hash(key + vec2f(3.0, 29.0)))
+                            .max(0.001);
+
+                            let center = hit.origin + point;
+                            let q = center + crate::sdf::rot(angle) * (p - center) / s;
+                            let operand = self.operand_hit(content, q, center, hit);
+                            let d = operand.min_distance * s;
+                            adjust_distances_from(&operand, d, hit);
+                            distance = distance.min(d);
+                        }
+                    }
+                    hit.last_size = dim;
+                }
+                Radial if !self.nodes[index].links.is_empty() => {
+                    let content = self.nodes[index].links[0] as usize;
+                    let dim = self.get_content_dim(content);
+
+                    let center = hit.origin
+                        + vec2f(
+                            self.get_value_default(
+                                index,
+                                FTValueRole::X,
+                                vec![hit.face.x / 2.0],
+                                hit,
+                            )[0],
+                            self.get_value_default(
+                                index,
+                                FTValueRole::Y,
+                                vec![hit.face.y / 2.0],
+                                hit,
+                            )[0],
+                        );
+                    let count = self.get_value_default(index, FTValueRole::Count, vec![8.0], hit)
+                        [0]
+                    .max(1.0)
+                    .round();
+                    let radius =
+                        self.get_value_default(index, FTValueRole::Radius, vec![0.3], hit)[0];
+                    let orient =
+                        self.get_value_default(index, FTValueRole::Orient, vec![1.0], hit)[0];
+
+                    // The sector of p, sector 0 is on the positive x axis.
+                    let step = std::f32::consts::TAU / count;
+                    let local = p - center;
+                    let sector = (local.y.atan2(local.x) / step).round().rem_euclid(count);
+                    let angle = sector * step;
+
+                    hit.working_col = sector as i32;
+                    hit.set_working_pattern_hash(self.hash(vec2f(sector, 0.0) + hit.working_seed));
+
+                    // Oriented content is evaluated in the frame of its sector, with its top
+                    // facing outward.
+                    distance = if orient != 0.0 {
+                        let q = crate::sdf::rot(std::f32::consts::FRAC_PI_2 - angle) * local;
+                        self.distance_jittered(
+                            index,
+                            content,
+                            center + q,
+                            center + vec2f(0.0, radius),
+                            hit.working_pattern_hash,
+                            hit,
+                        )
+                    } else {
+                        let pos = center + vec2f(angle.cos(), angle.sin()) * radius;
+                        self.distance_jittered(
+                            index,
+                            content,
+                            p,
+                            pos,
+                            hit.working_pattern_hash,
+                            hit,
+                        )
+                    };
+                    hit.last_size = Vec2f::one() * (2.0 * radius) + dim;
+                }
+                Random if !self.nodes[index].links.is_empty() => {
+                    let links = &self.nodes[index].links;
+                    let weights = self.get_value_default(index, FTValueRole::Weights, vec![], hit);
+
+                    // Derive the choice from the hash of the instance, but decorrelate it from
+                    // material modifiers which use the same hash. Parents which set no pattern
+                    // hash (Stack) only vary the seed per row.
+                    let key = if hit.has_working_pattern_hash {
+                        hit.working_pattern_hash
+                    } else {
+                        hit.working_seed
+                    };
+                    let choice = crate::sdf::hash21(vec2f(key * 113.0, 7.31));
+
+                    // Missing weights count as 1.
+                    let weight = |i: usize| weights.get(i).copied().unwrap_or(1.0).max(0.0);
+                    let total: f32 = (0..links.len()).map(weight).sum();
+
+                    let mut content = links[links.len() - 1] as usize;
+                    let mut sum = 0.0;
+                    for (i, link) in links.iter().enumerate() {
+                        sum += weight(i);
+                        if choice * total < sum {
+                            content = *link as usize;
+                            break;
+                        }
+                    }
+
+                    distance = self.distance(content, p, pos, hit);
+                }
+                Union | Subtract | Intersect | SmoothUnion | SmoothSubtract | SmoothIntersect
+                    if !self.nodes[index].links.is_empty() =>
+                {
+                    let sub_role = &self.nodes[index].sub_role;
+                    let k = self.get_value_default(index, FTValueRole::Blend, vec![0.05], hit)[0];
+
+                    // The operands are centered on the position of the combination.
+                    let mut winner: Option<FTHitStruct> = None;
+                    for (i, content) in self.nodes[index].links.iter().enumerate() {
+                        let operand = self.operand_hit(*content as usize, p, pos, hit);
+
+                        // Skip empty operands (i.e. deleted by meta), without the first operand
+                        // only a union has something left to show.
+                        if operand.node.is_none() {
+                            if i == 0 && !matches!(sub_role, Union | SmoothUnion) {
+                                break;
+                            }
+                            continue;
+                        }
+
+                        if winner.is_none() {
+                            distance = operand.min_distance;
+                            winner = Some(operand);
+                            continue;
+                        }
+
+                        let a = distance;
+                        let b = operand.min_distance;
+
+                        // The distance of the combination and if the new operand wins. The cutter of
+                        // a subtraction only removes and never provides the material.
+                        let (d, wins) = match sub_role {
+                            Union => (a.min(b), b < a),
+                            Subtract => (a.max(-b), false),
+                            Intersect => (a.max(b), b > a),
+                            SmoothUnion => smooth_min(a, b, k),
+                            SmoothSubtract => (-smooth_min(-a, b, k).0, false),
+                            _ => {
+                                let (d, wins) = smooth_min(-a, -b, k);
+                                (-d, wins)
+                            }
+                        };
+
+                        distance = d;
+                        if wins {
+                            winner = Some(operand);
+                        }
+                    }
+
+                    // Resolve the material and the hashes from the winning operand.
+                    if let Some(winner) = winner {
+                        adjust_distances_from(&winner, distance, hit);
+                    }
+
+                    hit.last_size = self.get_dim_default(index);
+                }
+                Offset if !self.nodes[index].links.is_empty() => {
+                    let content = self.nodes[index].links[0] as usize;
+                    let offset = vec2f(
+                        self.get_value_default(index, FTValueRole::X, vec![0.0], hit)[0],
+                        self.get_value_default(index, FTValueRole::Y, vec![0.0], hit)[0],
+                    );
+                    let scale = self.offset_scale(index, hit.working_params());
+
+                    // Scale around the position of the content.
+                    let q = pos + (p - pos - offset) / scale;
+                    if scale == 1.0 {
+                        distance = self.distance(content, q, pos, hit);
+                    } else {
+                        let operand = self.operand_hit(content, q, pos, hit);
+                        distance = operand.min_distance * scale;
+                        if operand.node.is_some() {
+                            adjust_distances_from(&operand, distance, hit);
+                        }
+                        // Layouts use the average scale so that they do not change per hash.
+                        hit.last_size = operand.last_size
+                            * self.offset_scale(index, vec![(FTExpressionParam::Hash, 0.5)]);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+
+        distance
+    }
+
+    /// The value of the `variant` expression parameter. Noise functions offset their input by it
+    /// so that every global seed gets its own noise, kept small like the offset of `hash`.
+    pub fn variant(&self) -> f32 {
+        (self.seed % 1009) as f32 * 0.731 + (self.seed / 1009 % 1013) as f32 * 0.517
+    }
+
+    /// Hash the given position, mixed with the global seed.
+    #[inline(always)]
+    fn hash(&self, p: Vec2f) -> f32 {
+        if self.seed == 0 {
+            crate::sdf::hash21(p)
+        } else {
+            // Keep the offset small, hash21 loses precision for large coordinates.
+            let offset = vec2f(
+                (self.seed % 1009) as f32 * 7.31,
+                (self.seed / 1009 % 1013) as f32 * 5.17,
+            );
+            crate::sdf::hash21(p + offset)
+        }
+    }
+
+    /// Get a value from a node, value expressions are evaluated for the current pattern instance.
+    fn get_value(&self, index: usize, role: FTValueRole, hit: &FTHitStruct) -> Option<Vec<f32>> {
+        let node = &self.nodes[index];
+        if node.expressions.contains(FTExpressionRole::Value(role)) {
+            Some(node.get_value(role, vec![], &hit.working_params()))
+        } else {
+            node.values.get_option(role)
+        }
+    }
+
+    /// Get a value from a node, value expressions are evaluated for the current pattern instance.
+    fn get_value_default(
+        &self,
+        index: usize,
+        role: FTValueRole,
+        default: Vec<f32>,
+        hit: &FTHitStruct,
+    ) -> Vec<f32> {
+        let node = &self.nodes[index];
+        if node.expressions.contains(FTExpressionRole::Value(role)) {
+            node.get_value(role, default, &hit.working_params())
+        } else {
+            node.values.get(role, default)
+        }
+    }
+
+    /// Evaluates the content of a pattern with the per-instance jitter of the pattern applied, the
+    /// jitter expressions use the given instance hash.
+    #[allow(clippy::too_many_arguments)]
+    fn distance_jittered(
+        &self,
+        index: usize,
+        content: usize,
+        p: Vec2f,
+        pos: Vec2f,
+        hash: f32,
+        hit: &mut FTHitStruct,
+    ) -> f32 {
+        let expressions = &self.nodes[index].expressions;
+        if !expressions.contains(FTExpressionRole::JitterX)
+            && !expressions.contains(FTExpressionRole::JitterY)
+            && !expressions.contains(FTExpressionRole::JitterRotation)
+            && !expressions.contains(FTExpressionRole::JitterScale)
+        {
+            return self.distance(content, p, pos, hit);
+        }
+
+        let mut params = hit.working_params();
+        params[0] = (FTExpressionParam::Hash, hash);
+
+        let offset = vec2f(
+            expressions.eval(FTExpressionRole::JitterX, params.clone(), 0.0),
+            expressions.eval(FTExpressionRole::JitterY, params.clone(), 0.0),
+        );
+        let angle = expressions
+            .eval(FTExpressionRole::JitterRotation, params.clone(), 0.0)
+            .to_radians();
+        let scale = expressions
+            .eval(FTExpressionRole::JitterScale, params, 1.0)
+            .max(0.001);
+
+        // Rotate and scale around the position of the content.
+        let q = pos + crate::sdf::rot(-angle) * (p - pos - offset) / scale;
+        if scale == 1.0 {
+            self.distance(content, q, pos, hit)
+        } else {
+            let operand = self.operand_hit(content, q, pos, hit);
+            let distance = operand.min_distance * scale;
+            adjust_distances_from(&operand, distance, hit);
+            hit.last_size = operand.last_size;
+            distance
+        }
+    }
+
+    /// Evaluates the rounding, annular and rotation expressions of a shape.
+    fn shape_modifiers(&self, index: usize, hit: &FTHitStruct) -> (f32, f32, f32) {
+        let expressions = &self.nodes[index].expressions;
+        let params = hit.working_params();
+
+        (
+            expressions.eval(FTExpressionRole::Rounding, params.clone(), 0.0),
+            expressions.eval(FTExpressionRole::Annular, params.clone(), 0.0),
+            expressions.eval(FTExpressionRole::Rotation, params, 0.0),
+        )
+    }
+
+    /// Evaluates an operand of a boolean combination in isolation, the min distance of the
+    /// returned hit is the distance of the operand.
+    fn operand_hit(&self, index: usize, p: Vec2f, pos: Vec2f, hit: &FTHitStruct) -> FTHitStruct {
+        let mut operand = FTHitStruct {
+            distance: f32::MAX,
+            min_distance: f32::MAX,
+            node: None,
+            is_cut_out: false,
+            ..*hit
+        };
+        self.distance(index, p, pos, &mut operand);
+        operand
+    }
+
+    /// Evaluates the scale of an Offset pattern.
+    fn offset_scale(&self, index: usize, params: Vec<(FTExpressionParam, f32)>) -> f32 {
+        let scale = self.nodes[index]
+            .expressions
+            .eval(FTExpressionRole::Scale, params, 1.0);
+        scale.max(0.001)
+    }
+
+    /// Returns true if the node is positioned like a shape, Offset and Random patterns are
+    /// transparent.
+    fn is_shape(&self, index: usize) -> bool {
+        let node = &self.nodes[index];
+        if matches!(node.sub_role, Offset | Random) && !node.links.is_empty() {
+            self.is_shape(node.links[0] as usize)
+        } else {
+            node.is_shape()
+        }
+    }
+
+    /// Get the string property of a node (i.e. `fill = stretch`) or the default.
+    fn get_string(&self, index: usize, name: &str, default: &str) -> String {
+        self.nodes[index]
+            .map
+            .get(name)
+            .and_then(|values| values.first())
+            .map(|value| value.to_lowercase())
+            .unwrap_or(default.to_string())
+    }
+
+    /// Returns true if the node has tileable set.
+    fn is_tileable(&self, index: usize) -> bool {
+        self.nodes[index]
+            .values
+            .get(FTValueRole::Tileable, vec![0.0])[0]
+            != 0.0
+    }
+
+    /// Get the dimension of the content of a node, patterns report the dimension of their first content.
+    fn get_content_dim(&self, index: usize) -> Vec2f {
+        if self.nodes[index].sub_role == Offset && !self.nodes[index].links.is_empty() {
+            self.get_content_dim(self.nodes[index].links[0] as usize)
+                * self.offset_scale(index, vec![(FTExpressionParam::Hash, 0.5)])
+        } else if self.nodes[index].role == Pattern
+            && !self.is_shape(index)
+            && !self.nodes[index].links.is_empty()
+        {
+            self.get_content_dim(self.nodes[index].links[0] as usize)
+        } else {
+            self.get_dim_default(index)
+        }
+    }
+
+    /// Get the dimension of a node.
+    fn get_dim_default(&self, index: usize) -> Vec2f {
+        let node = &self.nodes[index];
+        match node.sub_role {
+            // The union covers all operands, the other combinations lie within the first one. A
+            // random choice covers all of its choices.
+            Union | SmoothUnion | Random => node.links.iter().fold(Vec2f::zero(), |dim, link| {
+                max(dim, self.get_dim_default(*link as usize))
+            }),
+            Subtract | Intersect | SmoothSubtract | SmoothIntersect if !node.links.is_empty() => {
+                self.get_dim_default(node.links[0] as usize)
+            }
+            // Offset reports the size of its content at the average scale.
+            Offset if !node.links.is_empty() => {
+                self.get_dim_default(node.links[0] as usize)
+                    * self.offset_scale(index, vec![(FTExpressionParam::Hash, 0.5)])
+            }
+            _ => node.get_shape_dim(),
+        }
+    }
+}
+
+/// Adjust the distances for shapes
+#[inline(always)]
+fn adjust_distances(index: usize, distance: f32, hit: &mut FTHitStruct) {
+    if distance <= 0.0 {
+        hit.distance = distance;
+        hit.is_cut_out = false;
+    }
+    if distance < hit.min_distance {
+        hit.min_distance = distance;
+        hit.node = Some(index);
+
+        hit.seed = hit.working_seed;
+        hit.seed_id = hit.working_seed_id;
+
+        hit.pattern_hash = hit.working_pattern_hash;
+        hit.pattern_id = hit.working_pattern_id;
+
+        hit.row = hit.working_row;
+        hit.col = hit.working_col;
+        hit.uv = hit.working_uv;
+    }
+
+    if hit.node.is_none() {
+        hit.node = Some(index);
+
+        hit.seed = hit.working_seed;
+        hit.seed_id = hit.working_seed_id;
+
+        hit.pattern_hash = hit.working_pattern_hash;
+        hit.pattern_id = hit.working_pattern_id;
+
+        hit.row = hit.working_row;
+        hit.col = hit.working_col;
+        hit.uv = hit.working_uv;
+    }
+}
+
+/// Adjust the distances with the node and the hashes of an isolated hit.
+#[inline(always)]
+fn adjust_distances_from(from: &FTHitStruct, distance: f32, hit: &mut FTHitStruct) {
+    let Some(node) = from.node else {
+        return;
+    };
+    let working = *hit;
+    hit.working_seed = from.seed;
+    hit.working_seed_id = from.seed_id;
+    hit.working_pattern_hash = from.pattern_hash;
+    hit.working_pattern_id = from.pattern_id;
+    hit.working_row = from.row;
+    hit.working_col = from.col;
+    hit.working_uv = from.uv;
+    adjust_distances(node, distance, hit);
+    hit.working_seed = working.working_seed;
+    hit.working_seed_id = working.working_seed_id;
+    hit.working_pattern_hash = working.working_pattern_hash;
+    hit.working_pattern_id = working.working_pattern_id;
+    hit.working_row = working.working_row;
+    hit.working_col = working.working_col;
+    hit.working_uv = working.working_uv;
+}
+
+/// Polynomial smooth minimum, returns the distance and if `b` has the larger influence.
+#[inline(always)]
+fn smooth_min(a: f32, b: f32, k: f32) -> (f32, bool) {
+    if k <= 0.0 {
+        return (a.min(b), b < a);
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    (b + (a - b) * h - k * h * (1.0 - h), h < 0.5)
+}
+
+/// The number of cells which fit on the extent without shrinking the spacing included in the
+/// cell size. At least one and a multiple of `multiple`, so that alternating content wraps.
+fn fitting_count(extent: f32, cell: f32, multiple: usize) -> usize {
+    if cell <= 0.0 {
+        return multiple;
+    }
+    ((extent / (cell * multiple as f32)).floor() as usize).max(1) * multiple
+}
+
+/*
+float fillMask(float dist)
+{
+    return clamp(-dist, 0.0, 1.0);
+}
+
+float innerBorderMask(float dist, float width)
+{
+    //dist += 1.0;
+    return clamp(dist + width, 0.0, 1.0) - clamp(dist, 0.0, 1.0);
+}
+
+float outerBorderMask(float dist, float width)
+{
+    //dist += 1.0;
+    return clamp(dist, 0.0, 1.0) - clamp(dist - width, 0.0, 1.0);
+}
+*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+
+    fn compile(source: &str) -> FTContext {
+        Compiler::new().compile(source.to_string()).unwrap()
+    }
+
+    #[test]
+    fn rotates_boxes_around_their_center() {
+        let ctx = compile("let b = Shape<Box> : length = 0.4, height = 0.1, rotation = 90;");
+        let center = vec2f(0.5, 0.5);
+        let distance = |p: Vec2f| ctx.distance(0, p, center, &mut FTHitStruct::default());
+
+        // The rotated box stands upright at its position.
+        assert!(distance(center) < 0.0);
+        assert!(distance(center + vec2f(0.0, 0.15)) < 0.0);
+        assert!(distance(center + vec2f(0.15, 0.0)) > 0.0);
+    }
+
+    /// Render the node and check that the meta data agrees with every rendered pixel.
+    fn assert_meta_data_matches_render(ctx: &FTContext, name: &str) {
+        let (width, height) = (40, 40);
+        let mut buffer = vec![0; width * height * 4];
+        assert!(ctx.render_node(name, width, height, &mut buffer));
+
+        for y in 0..height {
+            for x in 0..width {
+                let offset = (y * width + x) * 4;
+                let drawn = buffer[offset..offset + 3] != [0, 0, 0];
+                let hit = ctx.meta_data_at_node(name, x as i32, y as i32, width, height);
+                assert_eq!(drawn, hit.is_some(), "{} at {}, {}", name, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn meta_data_matches_the_rendered_node() {
+        let ctx = compile(
+            "let mat = Material<BSDF> : color = #FF8040;\n\
+             let box = Shape<Box> : material = mat, length = 0.4, height = 0.2;\n\
+             let brick = Shape<Box> : material = mat, length = 0.2, height = 0.1;\n\
+             let row = Pattern<Repeat> : content = [brick];\n\
+             let stack = Pattern<Stack> : content = [row];",
+        );
+
+        for name in ["box", "stack"] {
+            assert_meta_data_matches_render(&ctx, name);
+
+            // The center of the image is covered by the centered box and by the stack.
+            let hit = ctx.meta_data_at_node(name, 500, 500, 1000, 1000).unwrap();
+            assert!(hit.node.is_some());
+            assert_ne!(
+                ctx.face_pixel_at_node(name, vec2f(0.5, 0.5)),
+                Some([0, 0, 0, 255])
+            );
+        }
+        assert_eq!(ctx.meta_data_at_node("box", 100, 500, 1000, 1000), None);
+    }
+
+    /// The hit of the content at p, shapes are centered at the origin.
+    fn hit_at(ctx: &FTContext, name: &str, face: Vec3f, p: Vec2f) -> FTHitStruct {
+        let mut hit = FTHitStruct {
+            face,
+            ..Default::default()
+        };
+        ctx.distance(ctx.variables[name], p, Vec2f::zero(), &mut hit);
+        hit
+    }
+
+    /// The distance and the hash of the content at p.
+    fn sample(ctx: &FTContext, name: &str, face: Vec3f, p: Vec2f) -> (f32, f32) {
+        let hit = hit_at(ctx, name, face, p);
+        (hit.distance, hit.pattern_hash)
+    }
+
+    #[test]
+    fn tileable_patterns_wrap_at_the_face_border() {
+        let ctx = compile(
+            "let brick = Shape<Box> : length = 0.2, height = 0.1;\n\
+             let row = Pattern<Repeat> : spacing = 0.01, tileable = 1, content = [brick];\n\
+             let both = Pattern<Repeat> : direction = both, spacing = 0.01, tileable = 1, \
+             content = [brick];\n\
+             let grid = Pattern<Grid> : spacing = 0.01, tileable = 1, content = [brick];",
+        );
+        let face = vec3f(1.0, 0.8, 0.1);
+
+        for (name, wraps_y) in [("row", false), ("both", true), ("grid", true)] {
+            for i in 0..200 {
+                let p = vec2f(
+                    0.0371 + (i % 20) as f32 * 0.0497,
+                    0.0213 + (i / 20) as f32 * 0.0387,
+                );
+                let (distance, hash) = sample(&ctx, name, face, p);
+
+                let mut offsets = vec![vec2f(face.x, 0.0)];
+                if wraps_y {
+                    offsets.push(vec2f(0.0, face.y));
+                }
+                for offset in offsets {
+                    let (wrapped_distance, wrapped_hash) = sample(&ctx, name, face, p + offset);
+                    assert!(
+                        (distance - wrapped_distance).abs() < 1e-4,
+                        "{} at {:?}",
+                        name,
+                        p
+                    );
+                    assert_eq!(hash, wrapped_hash, "{} at {:?}", name, p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tileable_patterns_keep_the_spacing() {
+        let ctx = compile(
+            "let brick = Shape<Box> : length = 0.2, height = 0.1;\n\
+             let row = Pattern<Repeat> : spacing = 0.01, content = [brick];\n\
+             let stack = Pattern<Stack> : spacing = 0.01, tileable = 1, content = [row];\n\
+             let grid = Pattern<Grid> : spacing = 0.01, tileable = 1, content = [brick];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+
+        // Along a line through the middle of the bricks the joints are at least the spacing.
+        for (name, vertical) in [("stack", true), ("grid", false), ("grid", true)] {
+            let mut gap = 0.0;
+            let mut widest: f32 = 0.0;
+            for i in 0..1000 {
+                let t = i as f32 / 1000.0;
+                let p = if vertical {
+                    vec2f(0.1, t)
+                } else {
+                    vec2f(t, 0.05)
+                };
+                if sample(&ctx, name, face, p).0 > 0.0 {
+                    gap += 0.001;
+                    widest = widest.max(gap);
+                } else {
+                    gap = 0.0;
+                }
+            }
+            assert!(widest >= 0.009, "{} joints are {}", name, widest);
+        }
+    }
+
+    #[test]
+    fn repeat_fills_the_face() {
+        let ctx = compile(
+            "let brick = Shape<Box> : length = 0.2, height = 0.1;\n\
+             let counted = Pattern<Repeat> : spacing = 0.01, count = 3, content = [brick];\n\
+             let stretched = Pattern<Repeat> : spacing = 0.01, fill = stretch, content = [brick];\n\
+             let centered = Pattern<Repeat> : spacing = 0.01, fill = center, content = [brick];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+        let distance = |name: &str, x: f32| sample(&ctx, name, face, vec2f(x, 0.05)).0;
+
+        // Three bricks starting at the left border, centered at 0.1, 0.31 and 0.52.
+        assert!(distance("counted", 0.52) < 0.0);
+        assert!(distance("counted", 0.205) > 0.0);
+        assert!(distance("counted", 0.73) > 0.0);
+
+        // Four bricks, the spacing grows to 0.05 so that they span the face.
+        assert!(distance("stretched", 0.85) < 0.0);
+        assert!(distance("stretched", 0.24) > 0.0);
+        assert!(distance("stretched", 0.26) < 0.0);
+        assert!(distance("stretched", 0.97) > 0.0);
+
+        // Four bricks with the requested spacing and equal margins on both sides.
+        assert!(distance("centered", 0.05) > 0.0);
+        assert!(distance("centered", 0.185) < 0.0);
+        for x in [0.03, 0.12, 0.29, 0.4, 0.47] {
+            assert!((distance("centered", x) - distance("centered", 1.0 - x)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn stack_fills_the_face() {
+        let ctx = compile(
+            "let brick = Shape<Box> : length = 1.0, height = 0.1;\n\
+             let counted = Pattern<Stack> : spacing = 0.01, count = 3, content = [brick];\n\
+             let stretched = Pattern<Stack> : spacing = 0.01, fill = stretch, content = [brick];\n\
+             let centered = Pattern<Stack> : spacing = 0.01, fill = center, content = [brick];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+        let distance = |name: &str, y: f32| sample(&ctx, name, face, vec2f(0.5, y)).0;
+
+        // Three rows from the bottom.
+        assert!(distance("counted", 0.27) < 0.0);
+        assert!(distance("counted", 0.105) > 0.0);
+        assert!(distance("counted", 0.37) > 0.0);
+
+        // Nine rows, the spacing grows so that the last joint ends at the top of the face.
+        assert!(distance("stretched", 0.95) < 0.0);
+        assert!(distance("stretched", 0.995) > 0.0);
+        assert!(distance("stretched", 0.105) > 0.0);
+        assert!(distance("stretched", 0.115) < 0.0);
+
+        // Nine rows with margins of 0.01 on both sides.
+        assert!(distance("centered", 0.005) > 0.0);
+        assert!(distance("centered", 0.995) > 0.0);
+        for y in [0.03, 0.12, 0.29, 0.4, 0.47] {
+            assert!((distance("centered", y) - distance("centered", 1.0 - y)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn shapes_fit_their_bounds() {
+        let ctx = compile(
+            "let tri = Shape<Triangle> : length = 0.4;\n\
+             let down = Shape<Triangle> : length = 0.4, rotation = 180;\n\
+             let hex = Shape<Hexagon> : radius = 0.2, rounding = 0.05;\n\
+             let poly = Shape<Polygon> : radius = 0.2, sides = 8;\n\
+             let star = Shape<Star> : radius = 0.2;\n\
+             let capsule = Shape<Capsule> : length = 0.4, height = 0.1;\n\
+             let ellipse = Shape<Ellipse> : length = 0.4, height = 0.2;\n\
+             let ring = Shape<Ring> : radius = 0.2, width = 0.05;",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+        let distance = |name: &str, p: Vec2f| hit_at(&ctx, name, face, p).distance;
+
+        // Shapes are centered on their position and stay inside their bounds.
+        for name in ["tri", "hex", "poly", "star", "capsule", "ellipse"] {
+            assert!(distance(name, Vec2f::zero()) < 0.0, "{}", name);
+
+            let half = ctx.nodes[ctx.variables[name]].get_shape_dim_with(&[]) / 2.0 + 0.01;
+            for p in [
+                vec2f(half.x, 0.0),
+                vec2f(-half.x, 0.0),
+                vec2f(0.0, half.y),
+                vec2f(0.0, -half.y),
+            ] {
+                assert!(distance(name, p) > 0.0, "{} at {:?}", name, p);
+            }
+        }
+
+        // The rotated triangle points down.
+        assert!(distance("tri", vec2f(0.1, -0.1)) < 0.0);
+        assert!(distance("tri", vec2f(0.1, 0.1)) > 0.0);
+        assert!(distance("down", vec2f(0.1, 0.1)) < 0.0);
+        assert!(distance("down", vec2f(0.1, -0.1)) > 0.0);
+
+        // The ring is a band of the given width at the border of its bounds.
+        assert!(distance("ring", Vec2f::zero()) > 0.0);
+        assert!(distance("ring", vec2f(0.0, 0.18)) < 0.0);
+        assert!(distance("ring", vec2f(-0.18, 0.0)) < 0.0);
+        assert!(distance("ring", vec2f(0.0, 0.14)) > 0.0);
+        assert!(distance("ring", vec2f(0.0, 0.21)) > 0.0);
+    }
+
+    #[test]
+    fn discs_support_rings_and_stacks() {
+        let ctx = compile(
+            "let ring = Shape<Disc> : radius = 0.2, annular = 0.05;\n\
+             let disc = Shape<Disc> : radius = 0.1;\n\
+             let column = Pattern<Stack> : count = 3, content = [disc];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+        let distance = |name: &str, p: Vec2f| hit_at(&ctx, name, face, p).distance;
+
+        // The annular value cuts a hole of twice its size into the disc.
+        assert!(distance("ring", Vec2f::zero()) > 0.0);
+        assert!(distance("ring", vec2f(0.0, 0.09)) > 0.0);
+        assert!(distance("ring", vec2f(0.15, 0.0)) < 0.0);
+        assert!(distance("ring", vec2f(0.0, -0.15)) < 0.0);
+        assert!(distance("ring", vec2f(0.21, 0.0)) > 0.0);
+
+        // Discs report their size, so a stack places them on top of each other.
+        let hit = hit_at(&ctx, "column", face, vec2f(0.1, 0.1));
+        assert_eq!(hit.last_size, vec2f(0.2, 0.2));
+        for y in [0.1, 0.3, 0.5] {
+            assert!(distance("column", vec2f(0.1, y)) < 0.0, "{}", y);
+        }
+        assert!(distance("column", vec2f(0.18, 0.2)) > 0.0);
+        assert!(distance("column", vec2f(0.1, 0.7)) > 0.0);
+    }
+
+    #[test]
+    fn boolean_patterns_take_the_material_of_the_winner() {
+        let ctx = compile(
+            "let red = Material<BSDF> : color = #FF0000;\n\
+             let blue = Material<BSDF> : color = #0000FF;\n\
+             let square = Shape<Box> : material = red, length = 0.4, height = 0.4;\n\
+             let small = Shape<Disc> : material = blue, radius = 0.1;\n\
+             let large = Shape<Disc> : material = blue, radius = 0.25;\n\
+             let union = Pattern<Union> : content = [square, large];\n\
+             let subtract = Pattern<Subtract> : content = [square, small];\n\
+             let intersect = Pattern<Intersect> : content = [square, large];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+        let sample = |name: &str, x: f32, y: f32| {
+            let hit = hit_at(&ctx, name, face, vec2f(x, y));
+            let color = BSDFMaterial::from_hit(&ctx, &hit).base_color;
+            (
+                hit.distance,
+                ctx.nodes[hit.node.unwrap()].name.clone(),
+                color,
+            )
+        };
+
+        // The deeper operand of a union wins.
+        let (distance, node, color) = sample("union", 0.0, 0.0);
+        assert!(distance < 0.0);
+        assert_eq!(node, "large");
+        assert_eq!(color[2], 1.0);
+        let (distance, node, color) = sample("union", 0.19, 0.19);
+        assert!(distance < 0.0);
+        assert_eq!(node, "square");
+        assert_eq!(color[0], 1.0);
+
+        // The cutter of a subtraction never provides the material.
+        assert!(sample("subtract", 0.0, 0.0).0 > 0.0);
+        let (distance, node, color) = sample("subtract", 0.15, 0.15);
+        assert!(distance < 0.0);
+        assert_eq!(node, "square");
+        assert_eq!(color[0], 1.0);
+
+        // The operand which defines the border of an intersection wins.
+        assert!(sample("intersect", 0.19, 0.19).0 > 0.0);
+        let (distance, node, _) = sample("intersect", 0.0, 0.19);
+        assert!(distance < 0.0);
+        assert_eq!(node, "square");
+        let (distance, node, color) = sample("intersect", 0.17, 0.17);
+        assert!(distance < 0.0);
+        assert_eq!(node, "large");
+        assert_eq!(color[2], 1.0);
+    }
+
+    #[test]
+    fn offsets_move_and_scale_their_content() {
+        let ctx = compile(
+            "let brick = Shape<Box> : length = 0.2, height = 0.1;\n\
+             let moved = Pattern<Offset> : x = 0.3, y = 0.1, content = [brick];\n\
+             let scaled = Pattern<Offset> : scale = 2, content = [brick];\n\
+             let column = Pattern<Stack> : count = 2, content = [scaled];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+        let distance = |name: &str, x: f32, y: f32| hit_at(&ctx, name, face, vec2f(x, y)).distance;
+
+        assert!(distance("moved", 0.3, 0.1) < 0.0);
+        assert!(distance("moved", 0.39, 0.14) < 0.0);
+        assert!(distance("moved", 0.0, 0.0) > 0.0);
+        assert!(distance("moved", 0.41, 0.1) > 0.0);
+
+        assert!(distance("scaled", 0.19, 0.09) < 0.0);
+        assert!(distance("scaled", 0.21, 0.0) > 0.0);
+        assert!(distance("scaled", 0.0, 0.11) > 0.0);
+
+        // The stack places the scaled bricks at the origin and advances by their size.
+        let hit = hit_at(&ctx, "column", face, vec2f(0.2, 0.1));
+        assert_eq!(hit.last_size, vec2f(0.4, 0.2));
+        assert!(distance("column", 0.2, 0.1) < 0.0);
+        assert!(distance("column", 0.39, 0.39) < 0.0);
+        assert!(distance("column", 0.41, 0.1) > 0.0);
+        assert!(distance("column", 0.2, 0.41) > 0.0);
+    }
+
+    #[test]
+    fn grids_report_rows_and_columns() {
+        let ctx = compile(
+            "let tile = Shape<Box> : length = 0.2, height = 0.1;\n\
+             let grid = Pattern<Grid> : spacing = 0.02, columns = 3, rows = 2, content = [tile];\n\
+             let shifted = Pattern<Grid> : spacing = 0.02, row_offset = 0.5, content = [tile];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+
+        // The cells are 0.22 by 0.12 with the tiles in their centers.
+        let hit = hit_at(&ctx, "grid", face, vec2f(0.55, 0.18));
+        assert!(hit.distance < 0.0);
+        assert_eq!((hit.col, hit.row), (2, 1));
+        let hit = hit_at(&ctx, "grid", face, vec2f(0.11, 0.06));
+        assert!(hit.distance < 0.0);
+        assert_eq!((hit.col, hit.row), (0, 0));
+        assert_ne!(
+            hit.pattern_id,
+            hit_at(&ctx, "grid", face, vec2f(0.33, 0.06)).pattern_id
+        );
+
+        // Joints and cells outside of the counts stay empty.
+        assert!(hit_at(&ctx, "grid", face, vec2f(0.005, 0.06)).distance > 0.0);
+        assert!(hit_at(&ctx, "grid", face, vec2f(0.77, 0.06)).distance > 0.0);
+        assert!(hit_at(&ctx, "grid", face, vec2f(0.11, 0.3)).distance > 0.0);
+
+        // Every second row is shifted by half a cell.
+        let hit = hit_at(&ctx, "shifted", face, vec2f(0.22, 0.18));
+        assert!(hit.distance < 0.0);
+        assert_eq!((hit.col, hit.row), (0, 1));
+        assert!(hit_at(&ctx, "shifted", face, vec2f(0.11, 0.18)).distance > 0.0);
+        assert!(hit_at(&ctx, "shifted", face, vec2f(0.11, 0.06)).distance < 0.0);
+    }
+
+    #[test]
+    fn parquet_planks_have_their_own_ids() {
+        let source = "let plank = Shape<Box> : length = 0.2, height = 0.05;\n\
+                      let block = Shape<Box> : length = 0.2, height = 0.1;\n\
+                      let herringbone = Pattern<Herringbone> : content = [plank];\n\
+                      let basketweave = Pattern<Basketweave> : content = [block];";
+        let ctx = compile(source);
+        let face = vec3f(1.0, 1.0, 0.1);
+
+        // The centers of neighbouring planks, horizontal and vertical ones.
+        let planks = [
+            ("herringbone", [(0.15, 0.075), (0.075, 0.2), (0.2, 0.125)]),
+            ("basketweave", [(0.3, 0.25), (0.3, 0.35), (0.15, 0.3)]),
+        ];
+        for (name, centers) in planks {
+            let ids: Vec<i32> = centers
+                .iter()
+                .map(|(x, y)| {
+                    let hit = hit_at(&ctx, name, face, vec2f(*x, *y));
+                    assert!(hit.distance < 0.0, "{} at {}, {}", name, x, y);
+                    hit.pattern_id
+                })
+                .collect();
+            assert!(ids[0] != ids[1] && ids[1] != ids[2] && ids[0] != ids[2]);
+
+            // Deleting a plank by its id keeps its neighbours.
+            let deleted = compile(&format!(
+                "{}\nlet gone = Meta<Delete> : content = [{}];",
+                source, ids[0]
+            ));
+            let (x, y) = centers[0];
+            assert!(hit_at(&deleted, name, face, vec2f(x, y)).distance > 0.0);
+            let (x, y) = centers[1];
+            assert!(hit_at(&deleted, name, face, vec2f(x, y)).distance < 0.0);
+        }
+    }
+
+    #[test]
+    fn hex_and_triangle_cells_have_their_own_ids() {
+        let ctx = compile(
+            "let hex = Shape<Hexagon> : radius = 0.1;\n\
+             let tri = Shape<Triangle> : length = 0.2;\n\
+             let honeycomb = Pattern<HexGrid> : content = [hex];\n\
+             let triangles = Pattern<TriGrid> : content = [tri];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+        let sqrt3 = 3.0_f32.sqrt();
+
+        // Hexagons with a circumradius of 0.1, odd rows lie between the even ones.
+        let hexagons = [
+            ((0.0, 0.0), (0, 0)),
+            ((0.05 * sqrt3, 0.15), (0, 1)),
+            ((0.1 * sqrt3, 0.0), (1, 0)),
+            ((0.05 * sqrt3, 0.45), (0, 3)),
+        ];
+        // The centroids of up and down triangles with sides of 0.2.
+        let height = 0.1 * sqrt3;
+        let triangles = [
+            ((0.1, height / 3.0), (0, 0)),
+            ((0.2, 2.0 * height / 3.0), (1, 0)),
+            ((0.3, height / 3.0), (2, 0)),
+            ((0.2, height + height / 3.0), (0, 1)),
+        ];
+
+        for (name, cells) in [("honeycomb", hexagons), ("triangles", triangles)] {
+            let mut ids = vec![];
+            for ((x, y), cell) in cells {
+                let hit = hit_at(&ctx, name, face, vec2f(x, y));
+                assert!(hit.distance < 0.0, "{} at {}, {}", name, x, y);
+                assert_eq!((hit.col, hit.row), cell, "{} at {}, {}", name, x, y);
+                assert!(!ids.contains(&hit.pattern_id));
+                ids.push(hit.pattern_id);
+            }
+        }
+    }
+
+    #[test]
+    fn voronoi_cells_are_shrunk_by_the_spacing() {
+        let ctx = compile(
+            "let regular = Pattern<Voronoi> : density = 4, jitter = 0, spacing = 0.02;\n\
+             let stones = Pattern<Voronoi> : density = 4, spacing = 0.02;",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+
+        // Without jitter the cells are the squares of a grid.
+        let hit = hit_at(&ctx, "regular", face, vec2f(0.125, 0.125));
+        assert!((hit.distance + 0.115).abs() < 1e-4);
+        assert!(hit_at(&ctx, "regular", face, vec2f(0.235, 0.125)).distance < 0.0);
+        assert!(hit_at(&ctx, "regular", face, vec2f(0.245, 0.125)).distance > 0.0);
+        assert!(hit_at(&ctx, "regular", face, vec2f(0.255, 0.125)).distance > 0.0);
+        assert_eq!(
+            hit_at(&ctx, "regular", face, vec2f(0.01, 0.01)).pattern_id,
+            hit.pattern_id
+        );
+        assert_ne!(
+            hit_at(&ctx, "regular", face, vec2f(0.375, 0.125)).pattern_id,
+            hit.pattern_id
+        );
+
+        // Jittered cells keep one id per cell, neighbouring cells are separated by the gap.
+        let mut ids = vec![];
+        for y in 0..10 {
+            let mut last: Option<(f32, i32)> = None;
+            for x in 0..1000 {
+                let p = vec2f(x as f32 / 1000.0, 0.05 + y as f32 * 0.1);
+                let hit = hit_at(&ctx, "stones", face, p);
+                if hit.distance > 0.0 {
+                    continue;
+                }
+                if let Some((last_x, last_id)) = last {
+                    if last_id != hit.pattern_id {
+                        assert!(p.x - last_x >= 0.019, "gap at {:?}", p);
+                    }
+                }
+                last = Some((p.x, hit.pattern_id));
+                if !ids.contains(&hit.pattern_id) {
+                    ids.push(hit.pattern_id);
+                }
+            }
+        }
+        assert!(ids.len() > 10);
+    }
+
+    #[test]
+    fn scatter_is_deterministic_and_keeps_the_minimum_distance() {
+        let source = "let pebble = Shape<Disc> : radius = 0.03;\n\
+                      let pebbles = Pattern<Scatter> : min_distance = 0.15, content = [pebble];";
+        let face = vec3f(1.0, 1.0, 0.1);
+
+        // The average position of the covered samples per instance id.
+        let instances = |ctx: &FTContext| {
+            let mut sums: std::collections::BTreeMap<i32, (Vec2f, f32)> = Default::default();
+            for y in 0..100 {
+                for x in 0..100 {
+                    let p = vec2f(x as f32 + 0.5, y as f32 + 0.5) / 100.0;
+                    let hit = hit_at(ctx, "pebbles", face, p);
+                    if hit.distance < 0.0 {
+                        let sum = sums.entry(hit.pattern_id).or_insert((Vec2f::zero(), 0.0));
+                        sum.0 += p;
+                        sum.1 += 1.0;
+                    }
+                }
+            }
+            sums.values()
+                .map(|(sum, count)| *sum / *count)
+                .collect::<Vec<Vec2f>>()
+        };
+
+        let centers = instances(&compile(source));
+        assert!(centers.len() > 10);
+        assert_eq!(centers, instances(&compile(source)));
+
+        let mut seeded = compile(source);
+        seeded.set_seed(1);
+        assert_ne!(centers, instances(&seeded));
+
+        for (i, a) in centers.iter().enumerate() {
+            for b in &centers[i + 1..] {
+                assert!(length(*a - *b) > 0.14, "{:?} and {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn radial_patterns_place_content_in_sectors() {
+        let ctx = compile(
+            "let spoke = Shape<Box> : length = 0.1, height = 0.05;\n\
+             let oriented = Pattern<Radial> : count = 4, radius = 0.3, content = [spoke];\n\
+             let upright = Pattern<Radial> : count = 4, radius = 0.3, orient = 0, \
+             content = [spoke];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+
+        // Sectors count counter clockwise from the positive x axis around the face center.
+        let mut ids = vec![];
+        for (sector, (x, y)) in [(0.8, 0.5), (0.5, 0.8), (0.2, 0.5), (0.5, 0.2)]
+            .into_iter()
+            .enumerate()
+        {
+            let hit = hit_at(&ctx, "oriented", face, vec2f(x, y));
+            assert!(hit.distance < 0.0);
+            assert_eq!(hit.col, sector as i32);
+            assert!(!ids.contains(&hit.pattern_id));
+            ids.push(hit.pattern_id);
+        }
+        assert!(hit_at(&ctx, "oriented", face, vec2f(0.5, 0.5)).distance > 0.0);
+
+        // Oriented content faces outward, the top of the spoke points along the x axis.
+        assert!(hit_at(&ctx, "oriented", face, vec2f(0.8, 0.54)).distance < 0.0);
+        assert!(hit_at(&ctx, "oriented", face, vec2f(0.84, 0.5)).distance > 0.0);
+        assert!(hit_at(&ctx, "upright", face, vec2f(0.84, 0.5)).distance < 0.0);
+        assert!(hit_at(&ctx, "upright", face, vec2f(0.8, 0.54)).distance > 0.0);
+    }
+
+    #[test]
+    fn random_patterns_follow_the_weights() {
+        let ctx = compile(
+            "let full = Shape<Box> : length = 0.04, height = 0.04;\n\
+             let half = Shape<Box> : length = 0.04, height = 0.04;\n\
+             let mixed = Pattern<Random> : weights = [3, 1], content = [full, half];\n\
+             let only = Pattern<Random> : weights = [0, 1], content = [full, half];\n\
+             let mixed_grid = Pattern<Grid> : spacing = 0.01, content = [mixed];\n\
+             let only_grid = Pattern<Grid> : spacing = 0.01, content = [only];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+
+        // The share of cells which pick the first content.
+        let share = |name: &str| {
+            let mut first = 0;
+            for y in 0..20 {
+                for x in 0..20 {
+                    let p = vec2f(x as f32 + 0.5, y as f32 + 0.5) * 0.05;
+                    let hit = hit_at(&ctx, name, face, p);
+                    assert!(hit.distance < 0.0);
+                    if hit.node == Some(ctx.variables["full"]) {
+                        first += 1;
+                    }
+                }
+            }
+            first as f32 / 400.0
+        };
+
+        assert_eq!(share("only_grid"), 0.0);
+        let mixed = share("mixed_grid");
+        assert!((0.65..0.85).contains(&mixed), "{}", mixed);
+    }
+
+    #[test]
+    fn jitter_moves_and_rotates_instances() {
+        let ctx = compile(
+            "let brick = Shape<Box> : length = 0.2, height = 0.05;\n\
+             let moved = Pattern<Repeat> : spacing = 0.1, jitter_x = 0.02, content = [brick];\n\
+             let rotated = Pattern<Repeat> : spacing = 0.1, jitter_rotation = 90, \
+             content = [brick];\n\
+             let hashed = Pattern<Repeat> : spacing = 0.1, jitter_x = hash * 0.05, \
+             content = [brick];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+        let distance = |name: &str, x: f32, y: f32| hit_at(&ctx, name, face, vec2f(x, y)).distance;
+
+        // The first brick spans 0.02 to 0.22 instead of 0 to 0.2.
+        assert!(distance("moved", 0.01, 0.025) > 0.0);
+        assert!(distance("moved", 0.21, 0.025) < 0.0);
+        assert!(distance("moved", 0.23, 0.025) > 0.0);
+
+        // Rotated around their centers the bricks stand upright.
+        assert!(distance("rotated", 0.1, 0.1) < 0.0);
+        assert!(distance("rotated", 0.18, 0.025) > 0.0);
+
+        // The offset follows the hash of each brick.
+        for k in 0..3 {
+            let left = k as f32 * 0.3;
+            let hash = hit_at(&ctx, "hashed", face, vec2f(left + 0.1, 0.025)).pattern_hash;
+            let inside: Vec<f32> = (0..300)
+                .map(|i| left + i as f32 * 0.001)
+                .filter(|x| distance("hashed", *x, 0.025) < 0.0)
+                .collect();
+            let center = (inside[0] + inside[inside.len() - 1]) / 2.0;
+            assert!((center - (left + 0.1 + hash * 0.05)).abs() < 0.002, "{}", k);
+        }
+    }
+
+    #[test]
+    fn variants_have_their_own_noise() {
+        let ctx = compile(
+            "let mat = Material<BSDF> : color = #404040, modifier = noise(x * 8, y * 8) * 0.5;\n\
+             let box = Shape<Box> : material = mat, length = 1.0, height = 1.0;",
+        );
+
+        let variants = ctx.render_variants(3, 16, 16);
+        assert_eq!(variants[0], ctx.render_variants(1, 16, 16)[0]);
+        assert_ne!(variants[0], variants[1]);
+        assert_ne!(variants[1], variants[2]);
+    }
+
+    #[test]
+    fn random_rows_pick_one_content_per_row() {
+        let ctx = compile(
+            "let a = Shape<Box> : length = 0.1, height = 0.1;\n\
+             let b = Shape<Box> : length = 0.1, height = 0.1;\n\
+             let row_a = Pattern<Repeat> : content = [a];\n\
+             let row_b = Pattern<Repeat> : content = [b];\n\
+             let mixed = Pattern<Random> : content = [row_a, row_b];\n\
+             let rows = Pattern<Stack> : count = 10, content = [mixed];",
+        );
+        let face = vec3f(1.0, 1.0, 0.1);
+
+        let mut picked = vec![];
+        for row in 0..10 {
+            let y = 0.05 + row as f32 * 0.1;
+            let node = hit_at(&ctx, "rows", face, vec2f(0.05, y)).node;
+            for x in 1..10 {
+                let hit = hit_at(&ctx, "rows", face, vec2f(0.05 + x as f32 * 0.1, y));
+                assert_eq!(hit.node, node, "row {} at {}", row, x);
+            }
+            picked.push(node);
+        }
+        assert!(picked.contains(&Some(ctx.variables["a"])));
+        assert!(picked.contains(&Some(ctx.variables["b"])));
+    }
+}
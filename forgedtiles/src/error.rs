@@ -0,0 +1,131 @@
+use crate::prelude::*;
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// The severity of a diagnostic.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum FTSeverity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for FTSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FTSeverity::Error => write!(f, "error"),
+            FTSeverity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A diagnostic reported by the compiler.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct FTError {
+    pub description: String,
+    /// The 1-based line, 0 if the diagnostic is not tied to the source.
+    pub line: u32,
+    /// The 1-based column, 0 if the diagnostic is not tied to the source.
+    pub column: u32,
+    /// The byte range of the offending code.
+    pub span: Range<usize>,
+    pub severity: FTSeverity,
+    /// The script the diagnostic belongs to, if compiled from a file.
+    pub file: Option<PathBuf>,
+}
+
+impl FTError {
+    pub fn new(description: String, line: u32) -> Self {
+        Self {
+            description,
+            line,
+            column: 0,
+            span: 0..0,
+            severity: FTSeverity::Error,
+            file: None,
+        }
+    }
+
+    /// Create an error for the given token.
+    pub fn at_token(description: String, token: &Token) -> Self {
+        Self {
+            description,
+            line: token.line as u32,
+            column: token.column as u32,
+            span: token.offset..token.offset + token.lexeme.len(),
+            severity: FTSeverity::Error,
+            file: None,
+        }
+    }
+
+    /// Returns true if this is an error and not a warning.
+    pub fn is_error(&self) -> bool {
+        self.severity == FTSeverity::Error
+    }
+
+    /// Renders the diagnostic against the source code, rustc style.
+    pub fn render(&self, source: &str, file_name: &str) -> String {
+        let mut out = format!("{}: {}\n", self.severity, self.description);
+
+        let line_text = if self.line > 0 {
+            source.lines().nth(self.line as usize - 1)
+        } else {
+            None
+        };
+
+        let Some(line_text) = line_text else {
+            out += &format!(" --> {}\n", file_name);
+            return out;
+        };
+
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        // Columns are byte based, convert them to characters for the marker.
+        let column = (self.column as usize).max(1) - 1;
+        let prefix = line_text.get(..column).unwrap_or(line_text);
+        let marker_start = prefix.chars().count();
+        let marker_len = line_text
+            .get(column..(column + self.span.len()).min(line_text.len()))
+            .map(|s| s.chars().count())
+            .unwrap_or(0)
+            .max(1);
+
+        out += &format!(
+            "{}--> {}:{}:{}\n",
+            gutter, file_name, self.line, self.column
+        );
+        out += &format!("{} |\n", gutter);
+        out += &format!("{} | {}\n", line_number, line_text);
+        out += &format!(
+            "{} | {}{}\n",
+            gutter,
+            " ".repeat(marker_start),
+            "^".repeat(marker_len)
+        );
+
+        out
+    }
+}
+
+impl fmt::Display for FTError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} (line {}, column {})",
+            self.severity, self.description, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for FTError {}
+
+/// Renders all diagnostics against the source code.
+pub fn render_errors(errors: &[FTError], source: &str, file_name: &str) -> String {
+    let mut out = String::new();
+    for error in errors {
+        out += &error.render(source, file_name);
+        out += "\n";
+    }
+    out
+}
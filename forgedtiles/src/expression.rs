@@ -1,8 +1,67 @@
 use crate::prelude::*;
 
-use exmex::Express;
+use crate::noise::*;
+use exmex::{BinOp, Express, FloatOpsFactory, MakeOperators, Operator};
 use FTExpressionRole::*;
 
+/// A parsed expression with our operators.
+pub type FTFlatEx = exmex::FlatEx<f32, FTOpsFactory>;
+
+/// The operators of expressions, the default exmex float operators and our procedural functions.
+/// Binary operators are called with two arguments, i.e. `noise(x, y)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FTOpsFactory;
+
+impl MakeOperators<f32> for FTOpsFactory {
+    fn make<'a>() -> Vec<Operator<'a, f32>> {
+        fn function(apply: fn(f32, f32) -> f32) -> BinOp<f32> {
+            BinOp {
+                apply,
+                prio: 0,
+                is_commutative: false,
+            }
+        }
+
+        let mut ops = FloatOpsFactory::<f32>::make();
+        ops.extend([
+            Operator::make_bin("noise", function(|x, y| value_noise2d(vec2f(x, y)))),
+            Operator::make_bin("perlin", function(|x, y| perlin2d(vec2f(x, y)))),
+            Operator::make_bin("simplex", function(|x, y| simplex2d(vec2f(x, y)))),
+            Operator::make_bin("fbm", function(|x, y| fbm2d(vec2f(x, y)))),
+            Operator::make_bin("voronoi", function(|x, y| voronoi2d(vec2f(x, y)))),
+            Operator::make_bin(
+                "rand",
+                function(|x, y| crate::sdf::hash21(vec2f(x, y) * 1731.7)),
+            ),
+            Operator::make_bin("step", function(|edge, x| if x < edge { 0.0 } else { 1.0 })),
+            Operator::make_unary("smoothstep", |t| {
+                // With equal edges the expansion of smoothstep(a, b, x) divides by zero, the
+                // infinities clamp to a step and 0 / 0 is the edge itself, where a step is 1.
+                let t = if t.is_nan() { 1.0 } else { t.clamp(0.0, 1.0) };
+                t * t * (3.0 - 2.0 * t)
+            }),
+        ]);
+        ops
+    }
+}
+
+impl FTOpsFactory {
+    /// The number of arguments of the given function, None if there is no such function.
+    pub fn arity(name: &str) -> Option<usize> {
+        Self::make()
+            .iter()
+            .find(|op| op.repr() == name && op.constant().is_none())
+            .map(|op| if op.has_bin() { 2 } else { 1 })
+    }
+}
+
+/// An error while parsing an expression.
+#[derive(Debug, PartialEq, Clone)]
+pub enum FTExpressionError {
+    Syntax(String),
+    UnknownVariable(String),
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
 pub enum FTExpressionRole {
     Extrusion,
@@ -10,6 +69,14 @@ pub enum FTExpressionRole {
     Rounding,
     Annular,
     Rotation,
+    RadiusModifier,
+    Scale,
+    JitterX,
+    JitterY,
+    JitterRotation,
+    JitterScale,
+    /// A value property (length, spacing, ...) which depends on the expression parameters.
+    Value(FTValueRole),
 
     Anisotropic,
     Metallic,
@@ -33,6 +100,12 @@ impl FTExpressionRole {
             "rounding" => Some(Rounding),
             "annular" => Some(Annular),
             "rotation" => Some(Rotation),
+            "radius_modifier" => Some(RadiusModifier),
+            "scale" => Some(Scale),
+            "jitter_x" => Some(JitterX),
+            "jitter_y" => Some(JitterY),
+            "jitter_rotation" => Some(JitterRotation),
+            "jitter_scale" => Some(JitterScale),
 
             "anisotropic" => Some(Anisotropic),
             "metallic" => Some(Metallic),
@@ -51,10 +124,30 @@ impl FTExpressionRole {
     }
 }
 
+/// The variables of an expression. `u`, `v` and `edge` describe the hit shape and are only
+/// known to material expressions, they are 0 in shape and pattern expressions.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
 pub enum FTExpressionParam {
     Hash,
     Thickness,
+    Row,
+    Col,
+    /// The instance seed.
+    Seed,
+    PatternId,
+    /// The position inside the shape, 0..1 over its length and height. Like `Edge` only known in
+    /// materials and in the extrusion.
+    U,
+    V,
+    /// The point in face space.
+    X,
+    Y,
+    FaceLength,
+    FaceHeight,
+    /// The distance to the border of the shape, positive inside.
+    Edge,
+    /// Changes with the global seed and is 0 without one, offsets the input of noise functions.
+    Variant,
 }
 
 impl FTExpressionParam {
@@ -62,6 +155,18 @@ impl FTExpressionParam {
         match s {
             "hash" => Some(FTExpressionParam::Hash),
             "thickness" => Some(FTExpressionParam::Thickness),
+            "row" => Some(FTExpressionParam::Row),
+            "col" => Some(FTExpressionParam::Col),
+            "seed" => Some(FTExpressionParam::Seed),
+            "pattern_id" => Some(FTExpressionParam::PatternId),
+            "u" => Some(FTExpressionParam::U),
+            "v" => Some(FTExpressionParam::V),
+            "x" => Some(FTExpressionParam::X),
+            "y" => Some(FTExpressionParam::Y),
+            "face_length" => Some(FTExpressionParam::FaceLength),
+            "face_height" => Some(FTExpressionParam::FaceHeight),
+            "edge" => Some(FTExpressionParam::Edge),
+            "variant" => Some(FTExpressionParam::Variant),
             _ => None,
         }
     }
@@ -69,7 +174,7 @@ impl FTExpressionParam {
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct FTExpressions {
-    pub expressions: Vec<(FTExpressionRole, exmex::FlatEx<f32>, Vec<FTExpressionParam>)>,
+    pub expressions: Vec<(FTExpressionRole, FTFlatEx, Vec<FTExpressionParam>)>,
 }
 
 impl Default for FTExpressions {
@@ -85,24 +190,33 @@ impl FTExpressions {
         }
     }
 
-    /// Add an expression.
-    pub fn add(&mut self, role: FTExpressionRole, expression: &str) {
-        match exmex::parse::<f32>(expression) {
-            Ok(expr) => {
-                let params_in_expr = expr.var_names();
-                let mut params = vec![];
+    /// Parse an expression, all variables have to be expression parameters.
+    pub fn parse(expression: &str) -> Result<FTFlatEx, FTExpressionError> {
+        let expr = FTFlatEx::parse(expression)
+            .map_err(|err| FTExpressionError::Syntax(err.to_string()))?;
 
-                for p in params_in_expr {
-                    if let Some(ftp) = FTExpressionParam::from_string(p) {
-                        params.push(ftp);
-                    }
-                }
-                self.expressions.push((role, expr, params));
-            }
-            Err(err) => {
-                println!("Expression error: {} for {:?}", err, role);
+        for name in expr.var_names() {
+            if FTExpressionParam::from_string(name).is_none() {
+                return Err(FTExpressionError::UnknownVariable(name.clone()));
             }
         }
+
+        Ok(expr)
+    }
+
+    /// Add a parsed expression.
+    pub fn add(&mut self, role: FTExpressionRole, expr: FTFlatEx) {
+        let params = expr
+            .var_names()
+            .iter()
+            .filter_map(|name| FTExpressionParam::from_string(name))
+            .collect();
+        self.expressions.push((role, expr, params));
+    }
+
+    /// Returns true if an expression for the given role exists.
+    pub fn contains(&self, role: FTExpressionRole) -> bool {
+        self.expressions.iter().any(|(r, _, _)| *r == role)
     }
 
     /// Evaluate the expression of the given role or return the default value.
@@ -114,25 +228,145 @@ impl FTExpressions {
     ) -> f32 {
         for (r, expr, params) in &self.expressions {
             if *r == role {
-                // Only use the variables which are contained in the expression
+                // Only use the variables which are contained in the expression, in the
+                // (alphabetical) order of the expression.
                 let mut out_params = vec![];
-                for (prole, pvalue) in &parameters {
-                    if params.contains(prole) {
-                        out_params.push(*pvalue);
-                    }
+                for prole in params {
+                    let value = parameters
+                        .iter()
+                        .find(|(role, _)| role == prole)
+                        .map(|(_, value)| *value)
+                        .unwrap_or_default();
+                    out_params.push(value);
                 }
 
-                match expr.eval(&out_params) {
-                    Ok(val) => {
-                        return val;
-                    }
-                    Err(err) => {
-                        println!("Error in expression '{:?}' : {}.", role, err);
-                        return default;
-                    }
-                }
+                // Expressions are validated when compiled, never report from the render loop.
+                return expr.eval(&out_params).unwrap_or(default);
             }
         }
         default
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use FTExpressionParam::*;
+
+    fn eval(expression: &str, parameters: Vec<(FTExpressionParam, f32)>) -> f32 {
+        let mut expressions = FTExpressions::new();
+        expressions.add(Modifier, FTExpressions::parse(expression).unwrap());
+        expressions.eval(Modifier, parameters, f32::NAN)
+    }
+
+    #[test]
+    fn evaluates_parameters_by_name() {
+        let all = [
+            Hash, Thickness, Row, Col, Seed, PatternId, U, V, X, Y, FaceLength, FaceHeight, Edge,
+            Variant,
+        ];
+        // Pass the parameters in reverse order, they are matched by name and not by position.
+        let parameters: Vec<(FTExpressionParam, f32)> = all
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, param)| (*param, i as f32 + 1.0))
+            .collect();
+
+        for name in [
+            "hash",
+            "thickness",
+            "row",
+            "col",
+            "seed",
+            "pattern_id",
+            "u",
+            "v",
+            "x",
+            "y",
+            "face_length",
+            "face_height",
+            "edge",
+            "variant",
+        ] {
+            let param = FTExpressionParam::from_string(name).unwrap();
+            let index = all.iter().position(|p| *p == param).unwrap();
+            assert_eq!(
+                eval(name, parameters.clone()),
+                index as f32 + 1.0,
+                "{}",
+                name
+            );
+        }
+
+        let combined = "col - row + (face_length - face_height) * 100 + (u - v) * 10 + x / y \
+                        + hash * thickness + seed - pattern_id + edge";
+        let expected =
+            (4.0 - 3.0) + (11.0 - 12.0) * 100.0 + (7.0 - 8.0) * 10.0 + 9.0 / 10.0 + 1.0 * 2.0 + 5.0
+                - 6.0
+                + 13.0;
+        assert!((eval(combined, parameters) - expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn missing_parameters_are_zero() {
+        assert_eq!(eval("hash + 1", vec![(Row, 5.0)]), 1.0);
+    }
+
+    #[test]
+    fn evaluates_functions() {
+        let p = vec![(X, 1.3), (Y, 2.7)];
+
+        assert_eq!(
+            eval("noise(x, y)", p.clone()),
+            value_noise2d(vec2f(1.3, 2.7))
+        );
+        assert_eq!(eval("perlin(x, y)", p.clone()), perlin2d(vec2f(1.3, 2.7)));
+        assert_eq!(eval("simplex(x, y)", p.clone()), simplex2d(vec2f(1.3, 2.7)));
+        assert_eq!(eval("fbm(x, y)", p.clone()), fbm2d(vec2f(1.3, 2.7)));
+        assert_eq!(eval("voronoi(x, y)", p.clone()), voronoi2d(vec2f(1.3, 2.7)));
+
+        let r = eval("rand(x, y)", p.clone());
+        assert!((0.0..1.0).contains(&r));
+        assert_eq!(eval("rand(x, y)", p.clone()), r);
+        assert_ne!(eval("rand(y, x)", p.clone()), r);
+
+        assert_eq!(eval("step(2, x)", p.clone()), 0.0);
+        assert_eq!(eval("step(1, x)", p.clone()), 1.0);
+        assert_eq!(eval("smoothstep(x - 1.3)", p.clone()), 0.0);
+        assert_eq!(eval("smoothstep(0.5)", p.clone()), 0.5);
+        assert_eq!(eval("smoothstep(x)", p.clone()), 1.0);
+        assert_eq!(eval("(x) max ((y) min (2))", p), 2.0);
+    }
+
+    #[test]
+    fn noise_is_in_range() {
+        for i in 0..100 {
+            let x = i as f32 * 0.37 - 10.0;
+            let y = i as f32 * 0.91 + 3.0;
+            for expression in [
+                "noise(x, y)",
+                "perlin(x, y)",
+                "simplex(x, y)",
+                "fbm(x, y)",
+                "voronoi(x, y)",
+                "rand(x, y)",
+            ] {
+                let value = eval(expression, vec![(X, x), (Y, y)]);
+                assert!((0.0..=1.0).contains(&value), "{} = {}", expression, value);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_unknown_variables() {
+        assert_eq!(
+            FTExpressions::parse("hash * weird"),
+            Err(FTExpressionError::UnknownVariable("weird".to_string()))
+        );
+        assert!(matches!(
+            FTExpressions::parse("hash * * 2"),
+            Err(FTExpressionError::Syntax(_))
+        ));
+    }
+}
@@ -21,6 +21,8 @@ pub struct FTHitStruct {
 
     pub working_pattern_id: i32,
     pub working_pattern_hash: f32,
+    /// A pattern set the working hash for the current instance.
+    pub has_working_pattern_hash: bool,
 
     pub working_seed: f32,
     pub working_seed_id: i32,
@@ -28,10 +30,29 @@ pub struct FTHitStruct {
     pub seed: f32,
     pub seed_id: i32,
 
+    /// The grid cell of the hit.
+    pub row: i32,
+    pub col: i32,
+
+    pub working_row: i32,
+    pub working_col: i32,
+
     pub is_cut_out: bool,
     pub shape_adder: f32,
 
     pub group_uv: Vec2f,
+
+    /// The point in face space.
+    pub point: Vec2f,
+    /// The position inside the hit shape, 0..1 over its length and height.
+    pub uv: Vec2f,
+    pub working_uv: Vec2f,
+
+    /// Patterns wrap exactly at the face borders.
+    pub tileable: bool,
+
+    /// The `variant` expression parameter of the context, see `FTContext::variant`.
+    pub variant: f32,
 }
 
 impl Default for FTHitStruct {
@@ -61,6 +82,7 @@ impl FTHitStruct {
 
             working_pattern_id: 0,
             working_pattern_hash: 0.0,
+            has_working_pattern_hash: false,
 
             working_seed: 0.0,
             working_seed_id: 0,
@@ -68,10 +90,71 @@ impl FTHitStruct {
             seed: 0.0,
             seed_id: 0,
 
+            row: 0,
+            col: 0,
+
+            working_row: 0,
+            working_col: 0,
+
             is_cut_out: false,
             shape_adder: 0.0,
 
             group_uv: Vec2f::zero(),
+
+            point: Vec2f::zero(),
+            uv: Vec2f::zero(),
+            working_uv: Vec2f::zero(),
+
+            tileable: false,
+
+            variant: 0.0,
         }
     }
+
+    /// Set the hash and the id of the pattern instance currently being evaluated.
+    pub fn set_working_pattern_hash(&mut self, hash: f32) {
+        self.working_pattern_hash = hash;
+        self.working_pattern_id = ((hash * 10000.0).floor() as i32) % 10000;
+        self.has_working_pattern_hash = true;
+    }
+
+    /// The expression parameters of the hit with the given hash.
+    pub fn params_with_hash(&self, hash: f32) -> Vec<(FTExpressionParam, f32)> {
+        vec![
+            (FTExpressionParam::Hash, hash),
+            (FTExpressionParam::Row, self.row as f32),
+            (FTExpressionParam::Col, self.col as f32),
+            (FTExpressionParam::Seed, self.seed),
+            (FTExpressionParam::PatternId, self.pattern_id as f32),
+            (FTExpressionParam::U, self.uv.x),
+            (FTExpressionParam::V, self.uv.y),
+            (FTExpressionParam::X, self.point.x),
+            (FTExpressionParam::Y, self.point.y),
+            (FTExpressionParam::FaceLength, self.face.x),
+            (FTExpressionParam::FaceHeight, self.face.y),
+            (FTExpressionParam::Edge, -self.min_distance),
+            (FTExpressionParam::Variant, self.variant),
+        ]
+    }
+
+    /// The expression parameters of the hit.
+    pub fn params(&self) -> Vec<(FTExpressionParam, f32)> {
+        self.params_with_hash(self.pattern_hash)
+    }
+
+    /// The expression parameters of the pattern currently being evaluated.
+    pub fn working_params(&self) -> Vec<(FTExpressionParam, f32)> {
+        vec![
+            (FTExpressionParam::Hash, self.working_pattern_hash),
+            (FTExpressionParam::Row, self.working_row as f32),
+            (FTExpressionParam::Col, self.working_col as f32),
+            (FTExpressionParam::Seed, self.working_seed),
+            (FTExpressionParam::PatternId, self.working_pattern_id as f32),
+            (FTExpressionParam::X, self.point.x),
+            (FTExpressionParam::Y, self.point.y),
+            (FTExpressionParam::FaceLength, self.face.x),
+            (FTExpressionParam::FaceHeight, self.face.y),
+            (FTExpressionParam::Variant, self.variant),
+        ]
+    }
 }
@@ -1,11 +1,14 @@
+pub mod atlas;
 pub mod bsdf;
 pub mod camera;
 pub mod compiler;
 pub mod context;
+pub mod error;
 pub mod expression;
 pub mod hit;
 pub mod material;
 pub mod node;
+pub mod noise;
 pub mod ray;
 pub mod scanner;
 pub mod sdf;
@@ -16,8 +19,9 @@ use std::path::PathBuf;
 pub mod prelude {
     pub use ::serde::{Deserialize, Serialize};
 
-    pub use crate::compiler::FTError;
+    pub use crate::atlas::*;
     pub use crate::context::FTContext;
+    pub use crate::error::*;
     pub use crate::expression::*;
     pub use crate::hit::*;
     pub use crate::material::*;
@@ -47,21 +51,23 @@ impl ForgedTiles {
     }
 
     /// Compile the given script.
-    pub fn compile(&self, path: PathBuf, file_name: String) -> Result<FTContext, FTError> {
+    pub fn compile(&self, path: PathBuf, file_name: String) -> Result<FTContext, Vec<FTError>> {
         let main_path = path.join(file_name.clone());
 
-        if let Ok(code) = std::fs::read_to_string(main_path) {
-            self.compile_code(code)
+        if let Ok(code) = std::fs::read_to_string(&main_path) {
+            let mut compiler = Compiler::new();
+            compiler.set_file(main_path);
+            compiler.compile(code)
         } else {
-            Err(FTError::new(
+            Err(vec![FTError::new(
                 format!("Error reading file `{}`", file_name),
                 0,
-            ))
+            )])
         }
     }
 
     /// Compile the given code.
-    pub fn compile_code(&self, code: String) -> Result<FTContext, FTError> {
+    pub fn compile_code(&self, code: String) -> Result<FTContext, Vec<FTError>> {
         let mut compiler = Compiler::new();
         compiler.compile(code)
     }
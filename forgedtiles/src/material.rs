@@ -115,11 +115,7 @@ impl BSDFMaterial {
                 let hash = hit.pattern_hash - 0.5;
                 let modifier = ctx.nodes[material]
                     .expressions
-                    .eval(
-                        FTExpressionRole::Modifier,
-                        vec![(FTExpressionParam::Hash, hash)],
-                        0.0,
-                    )
+                    .eval(FTExpressionRole::Modifier, hit.params_with_hash(hash), 0.0)
                     .clamp(0.0, 1.0);
                 mat.base_color[0] = c[0] + modifier;
                 mat.base_color[1] = c[1] + modifier;
@@ -127,75 +123,74 @@ impl BSDFMaterial {
                 // Anisotropic
                 mat.anisotropic = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::Anisotropic,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.0,
                 );
                 // Metallic
                 mat.metallic = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::Metallic,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.0,
                 );
                 // Roughness
                 mat.roughness = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::Roughness,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.5,
                 );
                 // Subsurface
                 mat.subsurface = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::Subsurface,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.0,
                 );
                 // Specular Tint
                 mat.specular_tint = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::SpecularTint,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.0,
                 );
                 // Sheen
                 mat.sheen = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::Sheen,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.0,
                 );
                 // Sheen Tint
                 mat.sheen_tint = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::SheenTint,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.0,
                 );
                 // Clearcoat
                 mat.clearcoat = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::Clearcoat,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.0,
                 );
                 // Clearcoat Gloss
                 mat.clearcoat_roughness = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::ClearcoatGloss,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.0,
                 );
                 // Emission
                 mat.emission = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::Emission,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.0,
                 ) * mat.base_color;
                 // Transmission
                 mat.spec_trans = ctx.nodes[material].expressions.eval(
                     FTExpressionRole::Transmission,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
+                    hit.params(),
                     0.0,
                 );
                 // IOR
-                mat.ior = ctx.nodes[material].expressions.eval(
-                    FTExpressionRole::IOR,
-                    vec![(FTExpressionParam::Hash, hit.pattern_hash)],
-                    1.5,
-                );
+                mat.ior =
+                    ctx.nodes[material]
+                        .expressions
+                        .eval(FTExpressionRole::IOR, hit.params(), 1.5);
                 // Texture name
                 if let Some(texture_name) = ctx.nodes[material].map.get("texture") {
                     if !texture_name.is_empty() {
@@ -13,11 +13,35 @@ pub enum NodeRole {
 pub enum NodeSubRole {
     Disc,
     Box,
+    Triangle,
+    Polygon,
+    Hexagon,
+    Star,
+    Capsule,
+    Ellipse,
+    Arc,
+    Ring,
 
     Repeat,
     Offset,
     Stack,
     Group,
+    Grid,
+    Herringbone,
+    Basketweave,
+    HexGrid,
+    TriGrid,
+    Voronoi,
+    Scatter,
+    Radial,
+    Random,
+
+    Union,
+    Subtract,
+    Intersect,
+    SmoothUnion,
+    SmoothSubtract,
+    SmoothIntersect,
 
     Floor,
     Left,
@@ -92,19 +116,58 @@ impl Node {
         }
     }
 
-    /// Return the length and height of the shape.
+    /// Returns true for shapes and for the boolean combinations of shapes, which are positioned like shapes.
+    pub fn is_shape(&self) -> bool {
+        self.role == Shape
+            || matches!(
+                self.sub_role,
+                Union | Subtract | Intersect | SmoothUnion | SmoothSubtract | SmoothIntersect
+            )
+    }
+
+    /// Get the values of the given role, a value expression is evaluated with the given parameters.
+    pub fn get_value(
+        &self,
+        role: FTValueRole,
+        default: Vec<f32>,
+        params: &[(FTExpressionParam, f32)],
+    ) -> Vec<f32> {
+        let expression = FTExpressionRole::Value(role);
+        if self.expressions.contains(expression) {
+            let default = default.first().copied().unwrap_or_default();
+            vec![self.expressions.eval(expression, params.to_vec(), default)]
+        } else {
+            self.values.get(role, default)
+        }
+    }
+
+    /// Return the length and height of the shape, for an average instance (a hash of 0.5).
     pub fn get_shape_dim(&self) -> Vec2f {
+        self.get_shape_dim_with(&[(FTExpressionParam::Hash, 0.5)])
+    }
+
+    /// Return the length and height of the shape for the given expression parameters.
+    pub fn get_shape_dim_with(&self, params: &[(FTExpressionParam, f32)]) -> Vec2f {
         let mut dim = Vec2f::zero();
 
         match &self.sub_role {
-            Disc => {
-                let radius = self.values.get(FTValueRole::Radius, vec![0.5])[0] * 2.0;
+            Disc | Polygon | Hexagon | Star | Arc | Ring => {
+                let radius = self.get_value(FTValueRole::Radius, vec![0.5], params)[0] * 2.0;
                 dim.x = radius;
                 dim.y = radius;
             }
+            Triangle => {
+                // Equilateral if no height is given.
+                dim.x = self.get_value(FTValueRole::Length, vec![1.0], params)[0];
+                dim.y = self.get_value(
+                    FTValueRole::Height,
+                    vec![dim.x * 3.0_f32.sqrt() / 2.0],
+                    params,
+                )[0];
+            }
             _ => {
-                dim.x = self.values.get(FTValueRole::Length, vec![1.0])[0];
-                dim.y = self.values.get(FTValueRole::Height, vec![1.0])[0];
+                dim.x = self.get_value(FTValueRole::Length, vec![1.0], params)[0];
+                dim.y = self.get_value(FTValueRole::Height, vec![1.0], params)[0];
             }
         }
 
@@ -0,0 +1,100 @@
+use crate::prelude::*;
+
+use crate::sdf::hash21;
+
+// Procedural noise for expressions, all functions return values in the range 0..1.
+
+/// A random unit gradient for the given lattice point.
+#[inline(always)]
+fn gradient(i: Vec2f) -> Vec2f {
+    let a = hash21(i) * std::f32::consts::TAU;
+    vec2f(a.cos(), a.sin())
+}
+
+/// Value noise, bilinear interpolated lattice hashes.
+pub fn value_noise2d(p: Vec2f) -> f32 {
+    let i = floor(p);
+    let f = p - i;
+    let u = f * f * (Vec2f::one() * 3.0 - f * 2.0);
+
+    let a = hash21(i);
+    let b = hash21(i + vec2f(1.0, 0.0));
+    let c = hash21(i + vec2f(0.0, 1.0));
+    let d = hash21(i + vec2f(1.0, 1.0));
+
+    lerp(lerp(a, b, u.x), lerp(c, d, u.x), u.y)
+}
+
+/// Perlin (gradient) noise.
+pub fn perlin2d(p: Vec2f) -> f32 {
+    let i = floor(p);
+    let f = p - i;
+    // Quintic fade
+    let u = f * f * f * (f * (f * 6.0 - Vec2f::one() * 15.0) + Vec2f::one() * 10.0);
+
+    let n00 = dot(gradient(i), f);
+    let n10 = dot(gradient(i + vec2f(1.0, 0.0)), f - vec2f(1.0, 0.0));
+    let n01 = dot(gradient(i + vec2f(0.0, 1.0)), f - vec2f(0.0, 1.0));
+    let n11 = dot(gradient(i + vec2f(1.0, 1.0)), f - vec2f(1.0, 1.0));
+
+    let n = lerp(lerp(n00, n10, u.x), lerp(n01, n11, u.x), u.y);
+    (0.5 + n * std::f32::consts::FRAC_1_SQRT_2).clamp(0.0, 1.0)
+}
+
+/// Simplex noise, see https://www.shadertoy.com/view/Msf3WH
+pub fn simplex2d(p: Vec2f) -> f32 {
+    const K1: f32 = 0.366_025_42; // (sqrt(3) - 1) / 2
+    const K2: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+
+    let i = floor(p + (p.x + p.y) * K1);
+    let a = p - i + (i.x + i.y) * K2;
+    let o = if a.x > a.y {
+        vec2f(1.0, 0.0)
+    } else {
+        vec2f(0.0, 1.0)
+    };
+    let b = a - o + K2;
+    let c = a - Vec2f::one() + 2.0 * K2;
+
+    let mut n = 0.0;
+    for (corner, offset) in [(a, Vec2f::zero()), (b, o), (c, Vec2f::one())] {
+        let h = (0.5 - dot(corner, corner)).max(0.0);
+        n += h * h * h * h * dot(corner, gradient(i + offset));
+    }
+
+    (0.5 + n * 35.0).clamp(0.0, 1.0)
+}
+
+/// Fractal brownian motion, five octaves of value noise.
+pub fn fbm2d(mut p: Vec2f) -> f32 {
+    let mut value = 0.0;
+    let mut amplitude = 0.5;
+    let mut total = 0.0;
+
+    for _ in 0..5 {
+        value += amplitude * value_noise2d(p);
+        total += amplitude;
+        // Rotate the octaves against each other to hide the lattice.
+        p = crate::sdf::rot(0.5) * p * 2.0 + vec2f(17.3, 5.1);
+        amplitude *= 0.5;
+    }
+
+    value / total
+}
+
+/// The distance to the closest point of a jittered grid of feature points (F1).
+pub fn voronoi2d(p: Vec2f) -> f32 {
+    let n = floor(p);
+    let f = p - n;
+
+    let mut closest = f32::MAX;
+    for j in -1..=1 {
+        for i in -1..=1 {
+            let g = vec2f(i as f32, j as f32);
+            let point = vec2f(hash21(n + g), hash21(n + g + vec2f(57.0, 113.0)));
+            closest = closest.min(length(g + point - f));
+        }
+    }
+
+    closest.min(1.0)
+}
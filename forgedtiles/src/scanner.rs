@@ -61,6 +61,7 @@ pub enum TokenType {
     True,
     Let,
     While,
+    Import,
     CodeBlock,
 
     Error,
@@ -71,6 +72,10 @@ pub enum TokenType {
 pub struct Token {
     pub kind: TokenType,
     pub line: usize,
+    /// The 1-based column of the first character of the token.
+    pub column: usize,
+    /// The byte offset of the token in the source code.
+    pub offset: usize,
     pub lexeme: String,
     pub indent: usize,
 }
@@ -82,18 +87,22 @@ impl Token {
             kind: TokenType::Error,
             lexeme: text,
             line: 0,
+            column: 0,
+            offset: 0,
             indent: 0,
         }
     }
 }
 
 #[allow(dead_code)]
+#[derive(Clone)]
 pub struct Scanner {
     keywords: HashMap<&'static str, TokenType>,
     code: String,
     start: usize,
     current: usize,
     line: usize,
+    line_start: usize,
     indent: usize,
 }
 
@@ -117,6 +126,7 @@ impl Scanner {
         keywords.insert("true", TokenType::True);
         keywords.insert("let", TokenType::Let);
         keywords.insert("while", TokenType::While);
+        keywords.insert("import", TokenType::Import);
 
         Scanner {
             keywords,
@@ -124,6 +134,7 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
             indent: 0,
         }
     }
@@ -173,6 +184,19 @@ impl Scanner {
         }
     }
 
+    /// Returns true if the next non whitespace character is the given one.
+    pub fn next_char_is(&self, c: u8) -> bool {
+        self.code.as_bytes()[self.current..]
+            .iter()
+            .find(|b| !b.is_ascii_whitespace())
+            == Some(&c)
+    }
+
+    /// Returns the source code between the given byte offsets.
+    pub fn source(&self, start: usize, end: usize) -> &str {
+        self.code.get(start..end).unwrap_or_default()
+    }
+
     fn is_at_end(&self) -> bool {
         self.current == self.code.len()
     }
@@ -186,6 +210,8 @@ impl Scanner {
             kind,
             lexeme: self.lexeme(),
             line: self.line,
+            column: self.start.saturating_sub(self.line_start) + 1,
+            offset: self.start,
             indent: self.indent,
         }
     }
@@ -210,6 +236,8 @@ impl Scanner {
             kind: TokenType::Error,
             lexeme: message,
             line: self.line,
+            column: self.start.saturating_sub(self.line_start) + 1,
+            offset: self.start,
             indent: self.indent,
         }
     }
@@ -245,6 +273,7 @@ impl Scanner {
                 b'\n' => {
                     self.line += 1;
                     self.advance();
+                    self.line_start = self.current;
                     self.indent = 0;
                     after_lf = true;
                 }
@@ -268,6 +297,7 @@ impl Scanner {
                     string = self.code[start..self.current].to_string();
                     self.advance();
                     self.line += 1;
+                    self.line_start = self.current;
                     break;
                 }
                 _ => {
@@ -301,6 +331,7 @@ impl Scanner {
                     newline = true;
                     self.advance();
                     self.line += 1;
+                    self.line_start = self.current;
                     indent = 0;
                 }
                 _ => {
@@ -22,3 +22,90 @@ pub fn sdf_box2d(p: Vec2f, pos: Vec2f, width: f32, height: f32, r: f32) -> f32 {
     let d = abs(p - pos) - vec2f(width, height) + vec2f(r, r);
     length(max(d, Vec2f::zero())) + min(max(d.x, d.y), 0.0) - r
 }
+
+/// Isosceles triangle with the base at the bottom, `width` and `height` are the full size.
+#[inline(always)]
+pub fn sdf_triangle2d(p: Vec2f, pos: Vec2f, width: f32, height: f32) -> f32 {
+    // Move the tip to the origin, https://iquilezles.org/articles/distfunctions2d/
+    let q = vec2f(width / 2.0, -height);
+    let mut p = p - pos - vec2f(0.0, height / 2.0);
+    p.x = p.x.abs();
+
+    let a = p - q * (dot(p, q) / dot(q, q)).clamp(0.0, 1.0);
+    let b = p - q * vec2f((p.x / q.x).clamp(0.0, 1.0), 1.0);
+    let s = -q.y.signum();
+    let d = min(
+        vec2f(dot(a, a), s * (p.x * q.y - p.y * q.x)),
+        vec2f(dot(b, b), s * (p.y - q.y)),
+    );
+    -d.x.sqrt() * d.y.signum()
+}
+
+/// Star with `n` points and the circumradius `r`, `m` in [2, n] controls the sharpness.
+#[inline(always)]
+pub fn sdf_star2d(p: Vec2f, pos: Vec2f, r: f32, n: f32, m: f32) -> f32 {
+    let an = std::f32::consts::PI / n;
+    let en = std::f32::consts::PI / m;
+    let acs = vec2f(an.cos(), an.sin());
+    let ecs = vec2f(en.cos(), en.sin());
+
+    let p = p - pos;
+    let bn = p.x.atan2(p.y).rem_euclid(2.0 * an) - an;
+    let mut p = vec2f(bn.cos(), bn.sin().abs()) * length(p);
+    p -= acs * r;
+    p += ecs * (-dot(p, ecs)).clamp(0.0, r * acs.y / ecs.y);
+    length(p) * p.x.signum()
+}
+
+/// Regular polygon with `n` sides and the circumradius `r`.
+#[inline(always)]
+pub fn sdf_polygon2d(p: Vec2f, pos: Vec2f, r: f32, n: f32) -> f32 {
+    sdf_star2d(p, pos, r, n, 2.0)
+}
+
+/// Horizontal capsule, `width` and `height` are the full size.
+#[inline(always)]
+pub fn sdf_capsule2d(p: Vec2f, pos: Vec2f, width: f32, height: f32) -> f32 {
+    let r = height.min(width) / 2.0;
+    let a = vec2f(-width / 2.0 + r, 0.0);
+    let b = vec2f(width / 2.0 - r, 0.0);
+
+    let pa = p - pos - a;
+    let ba = b - a;
+    let h = (dot(pa, ba) / dot(ba, ba).max(f32::EPSILON)).clamp(0.0, 1.0);
+    length(pa - ba * h) - r
+}
+
+/// Ellipse, `width` and `height` are the full size. This is an approximation.
+#[inline(always)]
+pub fn sdf_ellipse2d(p: Vec2f, pos: Vec2f, width: f32, height: f32) -> f32 {
+    let ab = vec2f(width / 2.0, height / 2.0);
+    let p = p - pos;
+
+    let k0 = length(p / ab);
+    let k1 = length(p / (ab * ab));
+    if k1 == 0.0 {
+        return -ab.x.min(ab.y);
+    }
+    k0 * (k0 - 1.0) / k1
+}
+
+/// Arc symmetric around the y axis with the total `aperture` in radians.
+#[inline(always)]
+pub fn sdf_arc2d(p: Vec2f, pos: Vec2f, aperture: f32, radius: f32, thickness: f32) -> f32 {
+    let sc = vec2f((aperture / 2.0).sin(), (aperture / 2.0).cos());
+    let mut p = p - pos;
+    p.x = p.x.abs();
+
+    if sc.y * p.x > sc.x * p.y {
+        length(p - sc * radius) - thickness
+    } else {
+        (length(p) - radius).abs() - thickness
+    }
+}
+
+/// Ring with the given radius and thickness.
+#[inline(always)]
+pub fn sdf_ring2d(p: Vec2f, pos: Vec2f, radius: f32, thickness: f32) -> f32 {
+    (length(p - pos) - radius).abs() - thickness
+}
@@ -2,7 +2,7 @@ use crate::prelude::*;
 
 use FTValueRole::*;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
 pub enum FTValueRole {
     Color,
     Width,
@@ -16,6 +16,26 @@ pub enum FTValueRole {
     Spacing,
     Offset,
     Cutout,
+    Tileable,
+    Sides,
+    Angle,
+    Blend,
+    SpacingX,
+    SpacingY,
+    Columns,
+    Rows,
+    RowOffset,
+    ColumnOffset,
+    Density,
+    Jitter,
+    Count,
+    MinDistance,
+    MinRotation,
+    MaxRotation,
+    MinScale,
+    MaxScale,
+    Orient,
+    Weights,
     X,
     Y,
     Z,
@@ -36,6 +56,26 @@ impl FTValueRole {
             "spacing" => Some(Spacing),
             "offset" => Some(Offset),
             "cutout" => Some(Cutout),
+            "tileable" => Some(Tileable),
+            "sides" => Some(Sides),
+            "angle" => Some(Angle),
+            "blend" => Some(Blend),
+            "spacing_x" => Some(SpacingX),
+            "spacing_y" => Some(SpacingY),
+            "columns" => Some(Columns),
+            "rows" => Some(Rows),
+            "row_offset" => Some(RowOffset),
+            "column_offset" => Some(ColumnOffset),
+            "density" => Some(Density),
+            "jitter" => Some(Jitter),
+            "count" => Some(Count),
+            "min_distance" => Some(MinDistance),
+            "min_rotation" => Some(MinRotation),
+            "max_rotation" => Some(MaxRotation),
+            "min_scale" => Some(MinScale),
+            "max_scale" => Some(MaxScale),
+            "orient" => Some(Orient),
+            "weights" => Some(Weights),
             "x" => Some(X),
             "y" => Some(Y),
             "z" => Some(Z),
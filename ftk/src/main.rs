@@ -1,54 +1,376 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use forgedtiles::prelude::*;
 use std::fs::File;
 use std::io::BufWriter;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-fn main() {
+/// The ForgedTiles command line tool.
+#[derive(Parser, Debug)]
+#[command(
+    name = "ftk",
+    version,
+    about = "Compile and render ForgedTiles scripts."
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Render the script into a PNG image.
+    Render(RenderCommand),
+    /// Render the output node of the script in 2D (shortcut for `render --mode 2d`).
+    Render2d(RenderArgs),
+    /// Compile the script and report errors without rendering.
+    Check(InputArgs),
+    /// Compile the script and print the resulting context as JSON.
+    Dump(DumpArgs),
+    /// Render faces (or the given nodes) into a PNG atlas plus a JSON manifest.
+    Atlas(AtlasArgs),
+}
+
+#[derive(Args, Debug)]
+struct InputArgs {
+    /// The script to compile.
+    #[arg(short, long, default_value = "main.ft")]
+    input: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct RenderCommand {
+    #[command(flatten)]
+    args: RenderArgs,
+
+    /// The render mode.
+    #[arg(short, long, value_enum, default_value_t = RenderMode::Bsdf)]
+    mode: RenderMode,
+}
+
+#[derive(Args, Debug)]
+struct RenderArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// The PNG file to write.
+    #[arg(short, long, default_value = "image.png")]
+    output: PathBuf,
+
+    /// The width of the image in pixels.
+    #[arg(long, default_value_t = 600, value_parser = parse_pixels)]
+    width: usize,
+
+    /// The height of the image in pixels.
+    #[arg(long, default_value_t = 600, value_parser = parse_pixels)]
+    height: usize,
+
+    /// The amount of samples for the path tracer.
+    #[arg(short, long, default_value_t = 2)]
+    samples: i32,
+
+    /// The global seed, different seeds create variations of the script.
+    #[arg(long, default_value_t = 0)]
+    seed: u32,
+
+    /// Render the given amount of variants with consecutive seeds.
+    /// The variant index is then appended to the output file name.
+    #[arg(long, default_value_t = 1, conflicts_with = "nodes")]
+    variants: u32,
+
+    /// Render the node with the given name instead of the output node (2D only).
+    /// Can be repeated, the node name is then appended to the output file name.
+    #[arg(short, long = "node")]
+    nodes: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct DumpArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Write the JSON into the given file instead of stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct AtlasArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// The PNG file to write.
+    #[arg(short, long, default_value = "atlas.png")]
+    output: PathBuf,
+
+    /// The JSON manifest to write, defaults to the output file with a `json` extension.
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+
+    /// The width of a cell in pixels.
+    #[arg(long, default_value_t = 128, value_parser = parse_pixels)]
+    cell_width: usize,
+
+    /// The height of a cell in pixels.
+    #[arg(long, default_value_t = 128, value_parser = parse_pixels)]
+    cell_height: usize,
+
+    /// The amount of transparent pixels around each cell.
+    #[arg(short, long, default_value_t = 0)]
+    padding: usize,
+
+    /// The amount of columns, a square layout if not set.
+    #[arg(short, long, default_value_t = 0)]
+    columns: usize,
+
+    /// The nodes to render, all faces if not set. Can be repeated.
+    #[arg(short, long = "node")]
+    nodes: Vec<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+enum RenderMode {
+    /// Render the output node in 2D via `FTContext::render`.
+    #[value(name = "2d")]
+    Flat,
+    /// Path trace all faces via `FTContext::render_bsdf_sample`.
+    Bsdf,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Render(command) => render(&command.args, command.mode),
+        Command::Render2d(args) => render(&args, RenderMode::Flat),
+        Command::Check(args) => match compile(&args.input) {
+            Some(_) => {
+                println!("{}: Ok", args.input.display());
+                ExitCode::SUCCESS
+            }
+            None => ExitCode::FAILURE,
+        },
+        Command::Dump(args) => dump(&args),
+        Command::Atlas(args) => atlas(&args),
+    }
+}
+
+/// Compile the given script, prints the error on failure.
+fn compile(input: &Path) -> Option<FTContext> {
     let ft = ForgedTiles::new();
 
-    let file_name = "main.ft";
-    let width = 600_usize;
-    let height = 600_usize;
+    let path = input.parent().map(Path::to_path_buf).unwrap_or_default();
+    let file_name = input
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    match ft.compile(path, file_name) {
+        Ok(ctx) => Some(ctx),
+        Err(errors) => {
+            for error in &errors {
+                // Errors can originate in imported scripts.
+                let file = error.file.clone().unwrap_or(input.to_path_buf());
+                let source = std::fs::read_to_string(&file).unwrap_or_default();
+                eprintln!("{}", error.render(&source, &file.display().to_string()));
+            }
+            None
+        }
+    }
+}
+
+/// Render the script with the given mode and save it as PNG.
+fn render(args: &RenderArgs, mode: RenderMode) -> ExitCode {
+    let Some(mut ctx) = compile(&args.input.input) else {
+        return ExitCode::FAILURE;
+    };
+    ctx.set_seed(args.seed);
+
+    let width = args.width;
+    let height = args.height;
 
-    let rc = ft.compile(PathBuf::new(), file_name.into());
+    if !args.nodes.is_empty() {
+        if mode != RenderMode::Flat {
+            eprintln!("Rendering individual nodes is only supported in 2d mode.");
+            return ExitCode::FAILURE;
+        }
+        return render_nodes(&ctx, args);
+    }
 
-    let samples = 2;
+    for variant in 0..args.variants {
+        ctx.set_seed(args.seed.wrapping_add(variant));
 
-    match rc {
-        Ok(ctx) => {
-            let mut buffer = vec![0; width * height * 4];
-            let start = get_time();
-            // ctx.render(width, height, &mut buffer);
-            for i in 0..samples {
-                ctx.render_bsdf_sample(width, height, &mut buffer, i);
+        let mut buffer = vec![0; width * height * 4];
+        let start = get_time();
+        match mode {
+            RenderMode::Flat => ctx.render(width, height, &mut buffer),
+            RenderMode::Bsdf => {
+                for i in 0..args.samples {
+                    ctx.render_bsdf_sample(width, height, &mut buffer, i);
+                }
             }
-            println!("Image rendered in {} ms", get_time() - start);
-
-            let path = "image.png";
-            let file = File::create(path).unwrap();
-            let w = BufWriter::new(file);
-
-            let mut encoder = png::Encoder::new(w, width as u32, height as u32);
-            encoder.set_color(png::ColorType::Rgba);
-            encoder.set_depth(png::BitDepth::Eight);
-            encoder
-                .add_text_chunk(
-                    "ForgedTiles".to_string(),
-                    "This image was procedurally generated by ForgedTiles.".to_string(),
-                )
-                .unwrap();
-
-            let mut writer = encoder.write_header().unwrap();
-
-            // let start = get_time();
-            // rpu.render(&mut buffer, (0, 0, width, height), width);
-            //println!("Image rendered in {} ms", get_time() - start);
-            writer.write_image_data(&buffer).unwrap(); // Save
         }
+        println!("Image rendered in {} ms", get_time() - start);
+
+        let output = if args.variants == 1 {
+            args.output.clone()
+        } else {
+            suffixed_path(&args.output, &variant.to_string())
+        };
+
+        if let Err(err) = save_png(&output, width, height, &buffer) {
+            eprintln!("Error writing `{}`: {}", output.display(), err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Render each of the given nodes in 2D into its own PNG.
+fn render_nodes(ctx: &FTContext, args: &RenderArgs) -> ExitCode {
+    let width = args.width;
+    let height = args.height;
+
+    for name in &args.nodes {
+        let mut buffer = vec![0; width * height * 4];
+        if !ctx.render_node(name, width, height, &mut buffer) {
+            eprintln!("Unknown node `{}`.", name);
+            return ExitCode::FAILURE;
+        }
+
+        let output = if args.nodes.len() == 1 {
+            args.output.clone()
+        } else {
+            suffixed_path(&args.output, name)
+        };
+
+        if let Err(err) = save_png(&output, width, height, &buffer) {
+            eprintln!("Error writing `{}`: {}", output.display(), err);
+            return ExitCode::FAILURE;
+        }
+        println!("Rendered `{}` into `{}`", name, output.display());
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Append `_suffix` to the file stem of the given path, i.e. `image.png` -> `image_brick.png`.
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or("png".to_string());
+
+    path.with_file_name(format!("{}_{}.{}", stem, suffix, extension))
+}
+
+/// Print the compiled context as JSON.
+fn dump(args: &DumpArgs) -> ExitCode {
+    let Some(ctx) = compile(&args.input.input) else {
+        return ExitCode::FAILURE;
+    };
+
+    let json = match serde_json::to_string_pretty(&ctx) {
+        Ok(json) => json,
+        Err(err) => {
+            eprintln!("Error serializing the context: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(output) = &args.output {
+        if let Err(err) = std::fs::write(output, json) {
+            eprintln!("Error writing `{}`: {}", output.display(), err);
+            return ExitCode::FAILURE;
+        }
+    } else {
+        println!("{}", json);
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Render the atlas and write the PNG and the manifest.
+fn atlas(args: &AtlasArgs) -> ExitCode {
+    let Some(ctx) = compile(&args.input.input) else {
+        return ExitCode::FAILURE;
+    };
+
+    let builder = FTAtlasBuilder {
+        cell_width: args.cell_width,
+        cell_height: args.cell_height,
+        padding: args.padding,
+        columns: args.columns,
+        nodes: args.nodes.clone(),
+    };
+
+    let start = get_time();
+    let atlas = match builder.build(&ctx) {
+        Ok(atlas) => atlas,
         Err(err) => {
-            println!("{:?}", err);
+            eprintln!("{}", err.description);
+            return ExitCode::FAILURE;
         }
+    };
+    println!(
+        "Atlas with {} tiles rendered in {} ms",
+        atlas.tiles.len(),
+        get_time() - start
+    );
+
+    if let Err(err) = save_png(&args.output, atlas.width, atlas.height, &atlas.buffer) {
+        eprintln!("Error writing `{}`: {}", args.output.display(), err);
+        return ExitCode::FAILURE;
     }
+
+    let manifest = args
+        .manifest
+        .clone()
+        .unwrap_or(args.output.with_extension("json"));
+    if let Err(err) = std::fs::write(&manifest, atlas.manifest()) {
+        eprintln!("Error writing `{}`: {}", manifest.display(), err);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Parse a size in pixels, which has to be at least one pixel.
+fn parse_pixels(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("has to be at least 1 pixel".to_string()),
+        Ok(size) => Ok(size),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Save the RGBA buffer as PNG.
+fn save_png(
+    path: &Path,
+    width: usize,
+    height: usize,
+    buffer: &[u8],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file = File::create(path)?;
+    let w = BufWriter::new(file);
+
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.add_text_chunk(
+        "ForgedTiles".to_string(),
+        "This image was procedurally generated by ForgedTiles.".to_string(),
+    )?;
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(buffer)?;
+
+    Ok(())
 }
 
 /// Gets the current time in milliseconds